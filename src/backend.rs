@@ -12,122 +12,592 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::{language, lsp_utils};
+use crate::{formatter, language, lsp_utils, quick_fix, semantic_tokens, snippets, workspace};
 use async_std::sync::Mutex;
 use halfbrown::HashMap;
-use serde_json::Value;
-use std::fs;
+use serde_json::{json, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tower_lsp::jsonrpc::{Error, Result};
+use tower_lsp::lsp_types::WorkspaceServerCapabilities;
 use tower_lsp::lsp_types::{
-    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
-    Diagnostic, DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
-    DocumentHighlight, DocumentHighlightParams, Documentation, ExecuteCommandParams, Hover,
-    HoverContents, HoverParams, HoverProviderCapability, InitializeParams, InitializeResult,
-    InitializedParams, InsertTextFormat, MarkupContent, MarkupKind, MessageType, OneOf, Position,
-    Range, ServerCapabilities, ServerInfo, SymbolInformation, TextDocumentSyncCapability,
-    TextDocumentSyncKind, Url, WorkDoneProgressOptions, WorkspaceFoldersServerCapabilities,
-    WorkspaceSymbolParams,
+    CodeAction, CodeActionKind, CodeActionOptions, CodeActionOrCommand, CodeActionParams,
+    CodeActionProviderCapability, CodeActionResponse, CodeDescription, CompletionItem,
+    CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse, Diagnostic,
+    DiagnosticRelatedInformation, DiagnosticSeverity, DidChangeConfigurationParams,
+    DidChangeTextDocumentParams, DidChangeWatchedFilesParams,
+    DidChangeWatchedFilesRegistrationOptions, DidChangeWorkspaceFoldersParams,
+    DidCloseTextDocumentParams, DidOpenTextDocumentParams, DidSaveTextDocumentParams,
+    DocumentFormattingParams, DocumentHighlight, DocumentHighlightKind, DocumentHighlightParams,
+    DocumentOnTypeFormattingOptions, DocumentOnTypeFormattingParams, DocumentRangeFormattingParams,
+    DocumentSymbol, DocumentSymbolParams, DocumentSymbolResponse, Documentation,
+    ExecuteCommandParams, FileChangeType, FileSystemWatcher, FormattingOptions, GlobPattern,
+    GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams,
+    HoverProviderCapability, InitializeParams, InitializeResult, InitializedParams,
+    InsertTextFormat, Location, MarkupContent, MarkupKind, MessageType, NumberOrString, OneOf,
+    ParameterInformation, ParameterLabel, Position, PrepareRenameResponse, Range, ReferenceParams,
+    Registration, RenameOptions, RenameParams, SemanticToken, SemanticTokens, SemanticTokensDelta,
+    SemanticTokensDeltaParams, SemanticTokensFullDeltaResult, SemanticTokensFullOptions,
+    SemanticTokensOptions, SemanticTokensParams, SemanticTokensRangeParams,
+    SemanticTokensRangeResult, SemanticTokensResult, SemanticTokensServerCapabilities,
+    ServerCapabilities, ServerInfo, SignatureHelp, SignatureHelpOptions, SignatureHelpParams,
+    SignatureInformation, SymbolInformation, TextDocumentIdentifier, TextDocumentPositionParams,
+    TextDocumentSyncCapability, TextDocumentSyncKind, TextDocumentSyncOptions,
+    TextDocumentSyncSaveOptions, TextEdit, Url, WorkDoneProgressOptions, WorkspaceEdit,
+    WorkspaceFoldersServerCapabilities, WorkspaceSymbolParams,
 };
-use tower_lsp::{jsonrpc::Result, lsp_types::WorkspaceServerCapabilities};
-use tower_lsp::{Client, LanguageServer};
+use tower_lsp::{Client, ClientSocket, LanguageServer, LspService};
 use tremor_script::arena::Arena;
-use tremor_script::highlighter::ErrorLevel;
+use tremor_script::ast::module::Manager;
+use tremor_script::path::ModulePath;
 
 // stores the latest state of the document as it changes (on edits)
 // TODO can add more fields here based on ast parsing
 #[derive(Debug, Default)]
 struct DocumentState {
     text: String,
+    version: i32,
+    // name of the tremor language the document is written in
+    language: &'static str,
+    // the semantic tokens last sent to the client, to compute deltas against
+    semantic_tokens: Option<SemanticTokens>,
+    // the diagnostics of the current version, once it is analysed
+    diagnostics: Vec<Diagnostic>,
 }
 
 // mapping of file uri to its server document state
 type State = HashMap<Url, DocumentState>;
 
+// mapping of file uri to the language and text of an open document
+type Documents = HashMap<Url, (&'static str, String)>;
+
+// a quick fix, or a rename to work out its edit for
+enum Fix {
+    Edit(WorkspaceEdit),
+    Rename(TextDocumentPositionParams, String),
+}
+
+// a symbol, along with the document it is defined in
+struct SymbolDefinition {
+    uri: Url,
+    text: String,
+    symbols: language::SymbolTable,
+    symbol: usize,
+}
+
+// what an identifier refers to
+enum Target {
+    Module(Vec<String>),
+    Symbol(SymbolDefinition),
+}
+
+// asks the client to pull all diagnostics again, which the lsp types have no
+// request for yet
+enum WorkspaceDiagnosticRefresh {}
+
+impl tower_lsp::lsp_types::request::Request for WorkspaceDiagnosticRefresh {
+    type Params = ();
+    type Result = ();
+    const METHOD: &'static str = "workspace/diagnostic/refresh";
+}
+
+// how many lines of a definition are shown on hover
+const MAX_HOVER_LINES: usize = 20;
+
+// how many names assigned to each other are followed to find a literal value
+const MAX_INDIRECTIONS: usize = 8;
+
+// default of how long a document has to be left unchanged before it is
+// analysed, in ms
+const DEFAULT_DEBOUNCE: u64 = 200;
+
 pub(crate) struct Backend {
     client: Client,
-    language: Box<dyn language::Language>,
-    state: Mutex<State>,
+    languages: HashMap<&'static str, Arc<dyn language::Language>>,
+    // language used for documents whose language can't be detected
+    default_language: &'static str,
+    // shared with the background analysis of changed documents, as are the
+    // index and whether diagnostics are pulled
+    state: Arc<Mutex<State>>,
+    workspace_folders: Arc<Mutex<Vec<Url>>>,
+    // symbols of the workspace folders and the module path, for symbol search
+    index: Arc<Mutex<workspace::Index>>,
+    // whether the client lets us register file watchers, to keep the index up to date
+    watch_files: AtomicBool,
+    // whether the client pulls diagnostics, in which case they aren't pushed
+    pull_diagnostics: Arc<AtomicBool>,
+    // how long a document has to be left unchanged before it is analysed, in ms
+    debounce: AtomicU64,
+    // whether the workspace files depending on a changed module are checked
+    // again when they aren't open
+    workspace_dependents: AtomicBool,
+    // how many spaces documents are indented by when formatted, or 0 to go by
+    // the client's options
+    indent_width: AtomicU64,
+    // the folders modules are loaded from
+    module_path: ModulePath,
 }
 
 impl Backend {
-    pub(crate) fn new(client: Client, language: Box<dyn language::Language>) -> Self {
+    pub(crate) fn new(
+        client: Client,
+        default_language: &'static str,
+        module_path: ModulePath,
+    ) -> Self {
+        // tremor loads the modules documents use from a module path of its own,
+        // which is global to the process
+        for mount in &module_path.mounts {
+            Manager::add_path(mount).expect("the module path can be extended");
+        }
         Self {
             client,
-            language,
-            state: Mutex::new(State::new()),
+            languages: language::all()
+                .map(|(name, language)| (name, Arc::from(language)))
+                .collect(),
+            default_language,
+            state: Arc::new(Mutex::new(State::new())),
+            workspace_folders: Arc::new(Mutex::new(Vec::new())),
+            index: Arc::new(Mutex::new(workspace::Index::default())),
+            watch_files: AtomicBool::new(false),
+            pull_diagnostics: Arc::new(AtomicBool::new(false)),
+            debounce: AtomicU64::new(DEFAULT_DEBOUNCE),
+            workspace_dependents: AtomicBool::new(false),
+            indent_width: AtomicU64::new(0),
+            module_path,
         }
     }
 
-    async fn update(&self, uri: Url, text: &str) {
-        // TODO implement update as well. also remove unwraps
+    /// The service for a backend, along with the methods tower-lsp has no
+    /// handlers for yet
+    pub(crate) fn service(
+        default_language: &'static str,
+        module_path: ModulePath,
+    ) -> (LspService<Self>, ClientSocket) {
+        LspService::build(|client| Self::new(client, default_language, module_path))
+            .custom_method("textDocument/diagnostic", Self::document_diagnostic)
+            .custom_method("workspace/diagnostic", Self::workspace_diagnostic)
+            .finish()
+    }
+
+    // applies the settings the client passes, as `initializationOptions` or in
+    // the `tremor` section of its configuration:
+    // - `diagnosticsDebounce`: ms a document is left unchanged before analysing it
+    // - `diagnoseWorkspaceDependents`: whether to publish diagnostics for the
+    //   files depending on a changed module that aren't open
+    // - `indentWidth`: spaces per level of indentation when formatting, instead
+    //   of the client's formatting options
+    fn configure(&self, settings: &Value) {
+        if let Some(debounce) = settings["diagnosticsDebounce"].as_u64() {
+            self.debounce.store(debounce, Ordering::Relaxed);
+        }
+        if let Some(dependents) = settings["diagnoseWorkspaceDependents"].as_bool() {
+            self.workspace_dependents
+                .store(dependents, Ordering::Relaxed);
+        }
+        if let Some(width) = settings["indentWidth"].as_u64() {
+            self.indent_width.store(width, Ordering::Relaxed);
+        }
+    }
+
+    fn language(&self, language_name: &str) -> &dyn language::Language {
+        self.shared_language(language_name).as_ref()
+    }
+
+    fn shared_language(&self, language_name: &str) -> &Arc<dyn language::Language> {
+        self.languages
+            .get(language_name)
+            .or_else(|| self.languages.get(self.default_language))
+            .expect("all languages are registered")
+    }
+
+    async fn update(&self, uri: Url, text: &str, version: i32, language: &'static str) {
         self.state.lock().await.insert(
             uri,
             DocumentState {
                 text: text.to_string(),
+                version,
+                language,
+                semantic_tokens: None,
+                diagnostics: Vec::new(),
             },
         );
     }
 
+    // applies the incremental edits from the client to the stored document,
+    // returning the updated text. stale edits (for a version that was already
+    // seen) and edits for unknown documents are rejected.
+    async fn apply_changes(
+        &self,
+        params: &DidChangeTextDocumentParams,
+    ) -> Option<(&'static str, String)> {
+        let mut state = self.state.lock().await;
+        let doc = state.get_mut(&params.text_document.uri)?;
+        if params.text_document.version <= doc.version {
+            return None;
+        }
+        for change in &params.content_changes {
+            lsp_utils::apply_change(&mut doc.text, change);
+        }
+        doc.version = params.text_document.version;
+        doc.diagnostics.clear();
+        Some((doc.language, doc.text.clone()))
+    }
+
     // LSP helper functions
 
-    fn get_diagnostics(&self, uri: &Url, text: &str) -> Vec<Diagnostic> {
+    fn diagnostics(language: &dyn language::Language, uri: &Url, text: &str) -> Vec<Diagnostic> {
         file_dbg("get_diagnostics", text);
 
         let mut diagnostics = Vec::new();
+        let problems = language.parse_errors(uri, text);
+        let code_description = Url::parse(language.docs_url())
+            .ok()
+            .map(|href| CodeDescription { href });
+        for problem in &problems {
+            let e = &problem.error;
+            let range = Range {
+                start: lsp_utils::to_lsp_position(&e.start()),
+                end: lsp_utils::to_lsp_position(&e.end()),
+            };
 
-        if let Some(errors) = self.language.parse_errors(uri, text) {
-            for e in &errors {
-                let range = Range {
-                    start: lsp_utils::to_lsp_position(&e.start()),
-                    end: lsp_utils::to_lsp_position(&e.end()),
-                };
+            let mut message = e.callout().to_string();
+            if let Some(hint) = &e.hint() {
+                // comma here splits the message into multiple lines
+                message = format!("{message}, Note: {hint}");
+            }
 
-                let mut message = e.callout().to_string();
-                if let Some(hint) = &e.hint() {
-                    // comma here splits the message into multiple lines
-                    message = format!("{message}, Note: {hint}");
-                }
+            diagnostics.push(Diagnostic {
+                range,
+                message,
+                code: Some(NumberOrString::String(problem.code.clone())),
+                code_description: code_description.clone(),
+                severity: Some(lsp_utils::to_lsp_severity(*e.level())),
+                source: Some("tremor-language-server".to_string()),
+                related_information: Self::get_related_information(language, uri, text, problem),
+                tags: lsp_utils::to_lsp_tags(problem),
+                ..Diagnostic::default()
+            });
+        }
+
+        diagnostics
+    }
 
-                if let ErrorLevel::Warning(class) = e.level() {
-                    message = format!("{class}: {message}");
+    // analyses a version of a document in the background, once it is left
+    // unchanged for `delay`: its diagnostics are stored and published and,
+    // unless it was just `indexed`, its symbols indexed. runs for versions
+    // superseded by then, or by the time the parsing is done, are dropped. no
+    // lock is held while parsing.
+    fn analyze(
+        &self,
+        uri: Url,
+        language: &str,
+        text: String,
+        version: i32,
+        delay: Duration,
+        indexed: bool,
+    ) {
+        let client = self.client.clone();
+        let language = self.shared_language(language).clone();
+        let state = self.state.clone();
+        let index = self.index.clone();
+        let workspace_folders = self.workspace_folders.clone();
+        let pull_diagnostics = self.pull_diagnostics.clone();
+        let module_path = self.module_path.clone();
+        async_std::task::spawn(async move {
+            let is_current =
+                || async { state.lock().await.get(&uri).map(|doc| doc.version) == Some(version) };
+            async_std::task::sleep(delay).await;
+            if !is_current().await {
+                return;
+            }
+            let push = !pull_diagnostics.load(Ordering::Relaxed);
+            let folders = Self::paths(&workspace_folders.lock().await);
+            let (symbols, diagnostics) = async_std::task::spawn_blocking({
+                let uri = uri.clone();
+                move || {
+                    let symbols = (!indexed).then(|| {
+                        language
+                            .symbols(&uri, &text)
+                            .filter(|_| uri.to_file_path().is_ok())
+                            .map(|symbols| {
+                                (
+                                    workspace::index_symbols(&uri, &text, &symbols),
+                                    workspace::index_uses(&symbols, &module_path, &folders),
+                                )
+                            })
+                    });
+                    let diagnostics = Self::diagnostics(language.as_ref(), &uri, &text);
+                    (symbols, diagnostics)
+                }
+            })
+            .await;
+            match state.lock().await.get_mut(&uri) {
+                Some(doc) if doc.version == version => doc.diagnostics = diagnostics.clone(),
+                _ => return,
+            }
+            match symbols {
+                Some(Some((symbols, uses))) => {
+                    index.lock().await.update(uri.clone(), symbols, uses);
                 }
+                Some(None) => index.lock().await.remove(&uri),
+                None => (),
+            }
+            if push {
+                client
+                    .publish_diagnostics(uri, diagnostics, Some(version))
+                    .await;
+            }
+        });
+    }
 
-                diagnostics.push(Diagnostic {
-                    range,
-                    message,
-                    severity: Some(lsp_utils::to_lsp_severity(*e.level())),
-                    source: Some("tremor-language-server".to_string()),
-                    ..Diagnostic::default()
-                });
+    // checks the files depending on the module at `uri` again, once it is
+    // saved or changed on disk, as that is where modules are loaded from. open
+    // files are analysed again, and the ones in the workspace too if so
+    // configured. clients pulling diagnostics are asked to pull them again.
+    async fn check_dependents(&self, uri: &Url) {
+        let dependents = self.index.lock().await.dependents(uri);
+        if dependents.is_empty() {
+            return;
+        }
+        if self.pull_diagnostics.load(Ordering::Relaxed) {
+            if let Err(e) = self
+                .client
+                .send_request::<WorkspaceDiagnosticRefresh>(())
+                .await
+            {
+                file_dbg("check_dependents_refresh", &e.to_string());
+            }
+            return;
+        }
+        let workspace_dependents = self.workspace_dependents.load(Ordering::Relaxed);
+        for dependent in dependents {
+            let open = self
+                .state
+                .lock()
+                .await
+                .get(&dependent)
+                .map(|doc| (doc.language, doc.text.clone(), Some(doc.version)));
+            let document = match open {
+                Some(document) => Some(document),
+                None if workspace_dependents => {
+                    Self::read(&dependent).map(|(language, text)| (language, text, None))
+                }
+                None => None,
+            };
+            match document {
+                Some((language, text, Some(version))) => {
+                    self.analyze(dependent, language, text, version, Duration::ZERO, true);
+                }
+                Some((language, text, None)) => {
+                    let language = self.shared_language(language).clone();
+                    let diagnostics = async_std::task::spawn_blocking({
+                        let uri = dependent.clone();
+                        move || Self::diagnostics(language.as_ref(), &uri, &text)
+                    })
+                    .await;
+                    self.client
+                        .publish_diagnostics(dependent, diagnostics, None)
+                        .await;
+                }
+                None => (),
             }
         }
+    }
 
-        diagnostics
+    // a full report of the diagnostics of a document, or an unchanged one if
+    // they are the same as the ones reported as `previous_result_id`
+    fn get_diagnostic_report(
+        language: &dyn language::Language,
+        uri: &Url,
+        text: &str,
+        previous_result_id: Option<&str>,
+    ) -> serde_json::Map<String, Value> {
+        let items = serde_json::to_value(Self::diagnostics(language, uri, text))
+            .unwrap_or_else(|_| Value::Array(vec![]));
+        let mut hasher = DefaultHasher::new();
+        items.to_string().hash(&mut hasher);
+        let result_id = format!("{:016x}", hasher.finish());
+
+        let mut report = serde_json::Map::new();
+        if previous_result_id == Some(result_id.as_str()) {
+            report.insert("kind".into(), "unchanged".into());
+        } else {
+            report.insert("kind".into(), "full".into());
+            report.insert("items".into(), items);
+        }
+        report.insert("resultId".into(), result_id.into());
+        report
+    }
+
+    // once the client pulls diagnostics, the ones pushed before are cleared,
+    // so they don't show up twice
+    async fn pull_diagnostics(&self) {
+        if self.pull_diagnostics.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        let uris: Vec<Url> = self.state.lock().await.keys().cloned().collect();
+        for uri in uris {
+            self.client.publish_diagnostics(uri, vec![], None).await;
+        }
+    }
+
+    // pull diagnostics are custom methods on plain JSON, as lsp-types has no
+    // types for them yet
+    async fn document_diagnostic(&self, params: Value) -> Result<Value> {
+        file_dbg("document_diagnostic", "document_diagnostic");
+        self.pull_diagnostics().await;
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .and_then(|uri| Url::parse(uri).ok())
+            .ok_or_else(|| Error::invalid_params("Missing textDocument.uri"))?;
+        let document = self.snapshot(&uri).await;
+        let document = match document {
+            Some(doc) => Some((doc.language, doc.text)),
+            None => Self::read(&uri),
+        };
+        let Some((language, text)) = document else {
+            return Ok(json!({"kind": "full", "items": []}));
+        };
+        let previous_result_id = params["previousResultId"].as_str();
+        Ok(Value::Object(Self::get_diagnostic_report(
+            self.language(language),
+            &uri,
+            &text,
+            previous_result_id,
+        )))
+    }
+
+    // the workspace files are read and parsed on a blocking thread, so other
+    // requests aren't held up meanwhile
+    async fn workspace_diagnostic(&self, params: Value) -> Result<Value> {
+        file_dbg("workspace_diagnostic", "workspace_diagnostic");
+        self.pull_diagnostics().await;
+        let previous_result_ids: HashMap<String, String> = params["previousResultIds"]
+            .as_array()
+            .map(|ids| {
+                ids.iter()
+                    .filter_map(|id| {
+                        Some((
+                            id["uri"].as_str()?.to_string(),
+                            id["value"].as_str()?.to_string(),
+                        ))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // open documents, then the other tremor files of the workspace folders
+        let mut documents: Vec<(Url, Option<i32>, &'static str, String)> = self
+            .state
+            .lock()
+            .await
+            .iter()
+            .map(|(uri, doc)| {
+                (
+                    uri.clone(),
+                    Some(doc.version),
+                    doc.language,
+                    doc.text.clone(),
+                )
+            })
+            .collect();
+        let folders = self.folders().await;
+        let languages = self.languages.clone();
+        let default_language = self.default_language;
+        let items = async_std::task::spawn_blocking(move || {
+            for file in workspace::files(&folders) {
+                let Ok(uri) = Url::from_file_path(&file) else {
+                    continue;
+                };
+                if documents.iter().any(|(open, ..)| open == &uri) {
+                    continue;
+                }
+                if let (Some(language), Ok(text)) =
+                    (language::detect("", &uri), std::fs::read_to_string(&file))
+                {
+                    documents.push((uri, None, language, text));
+                }
+            }
+
+            documents
+                .into_iter()
+                .map(|(uri, version, language, text)| {
+                    let language = languages
+                        .get(language)
+                        .or_else(|| languages.get(default_language))
+                        .expect("all languages are registered");
+                    let previous_result_id = previous_result_ids.get(uri.as_str());
+                    let mut report = Self::get_diagnostic_report(
+                        language.as_ref(),
+                        &uri,
+                        &text,
+                        previous_result_id.map(String::as_str),
+                    );
+                    report.insert("uri".into(), uri.to_string().into());
+                    report.insert("version".into(), json!(version));
+                    Value::Object(report)
+                })
+                .collect::<Vec<Value>>()
+        })
+        .await;
+        Ok(json!({ "items": items }))
+    }
+
+    // the original definition, for a duplicate definition
+    fn get_related_information(
+        language: &dyn language::Language,
+        uri: &Url,
+        text: &str,
+        problem: &language::Problem,
+    ) -> Option<Vec<DiagnosticRelatedInformation>> {
+        let name = problem.duplicate()?;
+        let (aid, tokens) = language.tokenize(uri, text).ok()?;
+        let declaration = language::first_declaration(&tokens, name);
+        drop(tokens);
+        unsafe { Arena::delte_index_this_is_really_unsafe_dont_use_it(aid).unwrap() };
+        Some(vec![DiagnosticRelatedInformation {
+            location: Location::new(uri.clone(), lsp_utils::to_lsp_range(text, &declaration?)),
+            message: format!("`{name}` is first defined here"),
+        }])
     }
 
-    fn get_completions(&self, uri: &Url, text: &str, position: Position) -> Vec<CompletionItem> {
+    fn get_completions(
+        &self,
+        language: &str,
+        uri: &Url,
+        text: &str,
+        position: Position,
+        folders: &[PathBuf],
+    ) -> Vec<CompletionItem> {
+        let language = self.language(language);
         let pre_position = Position {
             line: position.line,
-            character: position.character - 1,
+            character: position.character.saturating_sub(1),
         };
 
-        if let Ok((aid, tokens)) = self.language.tokenize(uri, text) {
-            if let Some(token) = lsp_utils::get_token(&tokens, pre_position) {
+        if let Ok((aid, tokens)) = language.tokenize(uri, text) {
+            if let Some((token, _)) =
+                lsp_utils::get_token(text, &tokens, pre_position).filter(|_| position.character > 0)
+            {
                 file_dbg("get_completions_token", &token);
                 // TODO eliminate the need for this by improving get_token()
                 let module_parts: Vec<&str> = token.rsplitn(2, "::").collect();
 
                 if let Some(module_name) = module_parts.get(1) {
                     file_dbg("get_completions_module_name", module_name);
-                    let res = self
-                        .language
+                    let res = language
                         .functions(uri, module_name)
                         .iter()
                         .map(|function_name| {
                             let mut detail = None;
                             let mut documentation = None;
                             let mut insert_text = None;
-                            if let Some(function_doc) = self
-                                .language
+                            if let Some(function_doc) = language
                                 .function_doc(uri, &format!("{module_name}::{function_name}"))
                             {
                                 file_dbg("get_completions_function_doc", &function_doc.description);
@@ -158,325 +628,4219 @@ impl Backend {
                                 ..CompletionItem::default()
                             }
                         })
-                        .collect();
-                    unsafe { Arena::delte_index_this_is_really_unsafe_dont_use_it(aid).unwrap() };
-                    return res;
+                        .collect::<Vec<CompletionItem>>();
+                    if !res.is_empty() {
+                        unsafe {
+                            Arena::delte_index_this_is_really_unsafe_dont_use_it(aid).unwrap();
+                        };
+                        return res;
+                    }
                 }
             }
-            unsafe { Arena::delte_index_this_is_really_unsafe_dont_use_it(aid).unwrap() }
+            let offset = lsp_utils::to_offset(text, position);
+            let word_start = text[..offset]
+                .trim_end_matches(|c: char| c.is_alphanumeric() || c == '_')
+                .len();
+            // fields, metadata and module members are neither variables nor
+            // keywords
+            let mut res = Vec::new();
+            if text[..word_start].ends_with("::") {
+                res = self
+                    .get_member_completions(language, uri, text, &tokens, word_start, folders)
+                    .unwrap_or_default();
+            } else if !text[..word_start].ends_with(['.', '$', ':']) {
+                let prefix = &text[..word_start];
+                let expected = language.expected_tokens(prefix);
+                // variables go where names do, as far as the document parses
+                if expected
+                    .as_ref()
+                    .map_or(true, |e| e.iter().any(|token| token == "`<ident>`"))
+                {
+                    res = Self::get_scope_completions(language, uri, text, &tokens, offset);
+                }
+                if let Some(expected) = expected {
+                    res.extend(Self::get_keyword_completions(language, prefix, &expected));
+                }
+            }
+            unsafe { Arena::delte_index_this_is_really_unsafe_dont_use_it(aid).unwrap() };
+            return res;
         }
 
         vec![]
     }
 
-    fn get_hover_content(
+    // the variables, consts and functions in scope at `offset`, along with the
+    // names that are in scope without being defined, like `event`
+    fn get_scope_completions(
+        language: &dyn language::Language,
+        uri: &Url,
+        text: &str,
+        tokens: &[language::TokenSpan],
+        offset: usize,
+    ) -> Vec<CompletionItem> {
+        let mut items: Vec<CompletionItem> = language::implicit_names(tokens, offset)
+            .into_iter()
+            .map(|name| CompletionItem {
+                label: name.to_string(),
+                kind: Some(CompletionItemKind::KEYWORD),
+                ..CompletionItem::default()
+            })
+            .collect();
+        let Some(symbols) = language.symbols(uri, text) else {
+            return items;
+        };
+        items.extend(
+            symbols
+                .visible(offset)
+                .into_iter()
+                .map(|i| Self::symbol_completion(&symbols, i)),
+        );
+        items
+    }
+
+    // the functions and consts of the user module the path ending at `offset`
+    // refers to, which is found in the module path or the workspace
+    // `folders`, along with their doc comments
+    fn get_member_completions(
         &self,
+        language: &dyn language::Language,
         uri: &Url,
         text: &str,
-        position: Position,
-    ) -> Option<MarkupContent> {
-        // TODO merge the repeated tokenize operation with get_completions()?
-        if let Ok((aid, tokens)) = self.language.tokenize(uri, text) {
-            if let Some(token) = lsp_utils::get_token(&tokens, position) {
-                file_dbg("get_hover_content_token", &token);
-                if let Some(function_doc) = self.language.function_doc(uri, &token) {
-                    file_dbg("get_hover_content_function_doc", &function_doc.description);
-                    let res = Some(MarkupContent {
-                        kind: MarkupKind::Markdown,
-                        value: function_doc.to_string(),
-                    });
-                    unsafe { Arena::delte_index_this_is_really_unsafe_dont_use_it(aid).unwrap() }
-                    return res;
-                }
+        tokens: &[language::TokenSpan],
+        offset: usize,
+        folders: &[PathBuf],
+    ) -> Option<Vec<CompletionItem>> {
+        let module = language.symbols(uri, text)?.module_before(tokens, offset)?;
+        let file = workspace::resolve_module(&module, &self.module_path, folders)?;
+        let text = std::fs::read_to_string(&file).ok()?;
+        let uri = Url::from_file_path(file).ok()?;
+        let language = self.language(language::detect("", &uri)?);
+        let symbols = language.symbols(&uri, &text)?;
+        let (aid, tokens) = language.tokenize(&uri, &text).ok()?;
+        let items = (0..symbols.symbols().len())
+            .filter(|i| {
+                symbols.is_exported(*i)
+                    && matches!(
+                        symbols.symbols()[*i].kind,
+                        language::Kind::Const | language::Kind::Function
+                    )
+            })
+            .map(|i| {
+                let mut item = Self::symbol_completion(&symbols, i);
+                item.documentation =
+                    language::doc_comment(&tokens, symbols.symbols()[i].selection.start).map(
+                        |doc| {
+                            Documentation::MarkupContent(MarkupContent {
+                                kind: MarkupKind::Markdown,
+                                value: doc,
+                            })
+                        },
+                    );
+                item
+            })
+            .collect();
+        drop(tokens);
+        unsafe { Arena::delte_index_this_is_really_unsafe_dont_use_it(aid).unwrap() };
+        Some(items)
+    }
+
+    // a completion for a variable, const or function, with placeholders for
+    // the arguments of functions
+    fn symbol_completion(symbols: &language::SymbolTable, i: usize) -> CompletionItem {
+        let symbol = &symbols.symbols()[i];
+        let mut item = CompletionItem {
+            label: symbol.name.clone(),
+            kind: Some(CompletionItemKind::VARIABLE),
+            detail: Some(symbol.kind.description().to_string()),
+            ..CompletionItem::default()
+        };
+        match symbol.kind {
+            language::Kind::Const => item.kind = Some(CompletionItemKind::CONSTANT),
+            language::Kind::Function => {
+                let args = symbols.arguments(i);
+                let args_snippet = args
+                    .iter()
+                    .enumerate()
+                    .map(|(i, arg)| format!("${{{}:{}}}", i + 1, arg))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                item.kind = Some(CompletionItemKind::FUNCTION);
+                item.detail = Some(format!("fn {}({})", symbol.name, args.join(", ")));
+                item.insert_text = Some(format!("{}({args_snippet})", symbol.name));
+                item.insert_text_format = Some(InsertTextFormat::SNIPPET);
             }
-            unsafe { Arena::delte_index_this_is_really_unsafe_dont_use_it(aid).unwrap() }
+            _ => (),
         }
-        None
+        item
     }
-}
 
-#[tower_lsp::async_trait]
-impl LanguageServer for Backend {
-    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
-        Ok(InitializeResult {
-            server_info: Some(ServerInfo {
-                name: "tremor-language-server".to_string(),
-                version: Some(env!("CARGO_PKG_VERSION").to_string()),
-            }),
-            capabilities: ServerCapabilities {
-                completion_provider: Some(CompletionOptions {
-                    trigger_characters: Some(vec![":".to_string()]),
-                    work_done_progress_options: WorkDoneProgressOptions::default(),
-                    ..CompletionOptions::default()
-                }),
-                hover_provider: Some(HoverProviderCapability::Simple(true)),
-                text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::FULL,
-                )),
-                workspace: Some(WorkspaceServerCapabilities {
-                    workspace_folders: Some(WorkspaceFoldersServerCapabilities {
-                        supported: Some(true),
-                        change_notifications: Some(OneOf::Left(true)),
-                    }),
-                    ..WorkspaceServerCapabilities::default()
-                }),
-                ..ServerCapabilities::default()
-            },
-        })
+    // the keywords among the tokens the grammar `expected` after `prefix`, and
+    // snippets for the constructs starting with them
+    fn get_keyword_completions(
+        language: &dyn language::Language,
+        prefix: &str,
+        expected: &[String],
+    ) -> Vec<CompletionItem> {
+        let mut keywords = snippets::keywords(expected);
+        // where names are expected, the builtin ones are completed along with
+        // the variables in scope
+        if expected.iter().any(|token| token == "`<ident>`") {
+            keywords.retain(|k| !matches!(*k, "event" | "state" | "args" | "window" | "group"));
+        }
+        // the tokens expected after the keywords that snippets go on from,
+        // parsed once for all the snippets starting with the same keyword
+        let mut following: HashMap<&str, Vec<String>> = HashMap::new();
+        let mut items = Vec::new();
+        for snippet in snippets::SNIPPETS {
+            let mut lead = snippet.lead.split(' ');
+            let Some(first) = lead.next().filter(|first| keywords.contains(first)) else {
+                continue;
+            };
+            if let Some(second) = lead.next() {
+                let expected = following.entry(first).or_insert_with(|| {
+                    language
+                        .expected_tokens(&format!("{prefix}{first}"))
+                        .unwrap_or_default()
+                });
+                if !expected.contains(&format!("`{second}`")) {
+                    continue;
+                }
+            }
+            items.push(CompletionItem {
+                label: snippet.lead.to_string(),
+                kind: Some(CompletionItemKind::SNIPPET),
+                detail: Some(snippet.detail.to_string()),
+                insert_text: Some(snippet.body.to_string()),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..CompletionItem::default()
+            });
+        }
+        for keyword in keywords {
+            if !items.iter().any(|item| item.label == keyword) {
+                items.push(CompletionItem {
+                    label: keyword.to_string(),
+                    kind: Some(CompletionItemKind::KEYWORD),
+                    ..CompletionItem::default()
+                });
+            }
+        }
+        items
     }
 
-    async fn initialized(&self, _: InitializedParams) {
-        file_dbg("initialized", "initialized");
+    fn get_signature_help(
+        &self,
+        language: &str,
+        uri: &Url,
+        text: &str,
+        position: Position,
+        folders: &[PathBuf],
+    ) -> Option<SignatureHelp> {
+        let language = self.language(language);
+        let offset = lsp_utils::to_offset(text, position);
+        let (aid, tokens) = language.tokenize(uri, text).ok()?;
+        let call = language::call_at(&tokens, offset);
+        // functions of the document itself are called by their name alone
+        let local = call.as_ref().and_then(|(path, _)| {
+            let [(_, name)] = path.as_slice() else {
+                return None;
+            };
+            let symbols = language.symbols(uri, text)?;
+            let symbol = &symbols.symbols()[symbols.symbol_at(name.start)?];
+            (symbol.kind == language::Kind::Function).then(|| {
+                (
+                    language::parameters(&tokens, symbol.selection.start),
+                    language::doc_comment(&tokens, symbol.selection.start),
+                )
+            })
+        });
+        drop(tokens);
+        unsafe { Arena::delte_index_this_is_really_unsafe_dont_use_it(aid).unwrap() };
 
-        // TODO check this from clients
-        //self.client.show_message(MessageType::Info, "Initialized Trill!").await;
-        self.client
-            .log_message(MessageType::INFO, "Initialized Trill!")
-            .await;
+        let (path, argument) = call?;
+        let (parameters, documentation) = match local {
+            Some(signature) => signature,
+            None => self.get_module_signature(language, uri, text, &path, folders)?,
+        };
+        let name: Vec<&str> = path.iter().map(|(name, _)| name.as_str()).collect();
+        let signature = Self::signature_information(&name.join("::"), &parameters, documentation);
+        // the rest of the arguments of variadic functions all go to `...`
+        let active = match parameters.last() {
+            Some(last) if last == "..." => argument.min(parameters.len() - 1),
+            _ => argument,
+        };
+        Some(SignatureHelp {
+            signatures: vec![signature],
+            active_signature: Some(0),
+            active_parameter: u32::try_from(active).ok(),
+        })
     }
 
-    // TODO do more here (as appropriate). manadatory implementations for the trait
-
-    async fn shutdown(&self) -> Result<()> {
-        file_dbg("shutdown", "shutdown");
-        Ok(())
+    // the parameters and doc comments of a function of a module, which is a
+    // user module in the module path or the workspace `folders`, or else a
+    // module of the standard library
+    fn get_module_signature(
+        &self,
+        language: &dyn language::Language,
+        uri: &Url,
+        text: &str,
+        path: &language::Path,
+        folders: &[PathBuf],
+    ) -> Option<(Vec<String>, Option<String>)> {
+        let ((name, _), module) = path.split_last()?;
+        let module = language.symbols(uri, text)?.expand(&module.to_vec());
+        let std_module: Vec<String> = std::iter::once("std".to_string())
+            .chain(module.iter().cloned())
+            .collect();
+        // intrinsic functions are declared, with their docs, in the modules of
+        // the standard library
+        let declared = [&module, &std_module].into_iter().find_map(|module| {
+            let file = workspace::resolve_module(module, &self.module_path, folders)?;
+            let text = std::fs::read_to_string(&file).ok()?;
+            let uri = Url::from_file_path(file).ok()?;
+            let language = self.language(language::detect("", &uri)?);
+            let symbols = language.symbols(&uri, &text)?;
+            let symbol = &symbols.symbols()[symbols.top_level(name)?];
+            if symbol.kind != language::Kind::Function {
+                return None;
+            }
+            let (aid, tokens) = language.tokenize(&uri, &text).ok()?;
+            let signature = (
+                language::parameters(&tokens, symbol.selection.start),
+                language::doc_comment(&tokens, symbol.selection.start),
+            );
+            drop(tokens);
+            unsafe { Arena::delte_index_this_is_really_unsafe_dont_use_it(aid).unwrap() };
+            Some(signature)
+        });
+        if declared.is_some() {
+            return declared;
+        }
+        // other functions of the registry only tell how many arguments they
+        // take. the registry names modules without their leading `std`, which
+        // `use std::string;` expands `string` to
+        let registry_module = match module.split_first() {
+            Some((std, rest)) if std == "std" => rest,
+            _ => &module,
+        };
+        let arity = language.function_arity(&registry_module.join("::"), name)?;
+        let mut parameters: Vec<String> = (1..=*arity.start()).map(|i| format!("arg{i}")).collect();
+        if *arity.end() == usize::MAX {
+            parameters.push("...".to_string());
+        } else {
+            parameters.extend((arity.start() + 1..=*arity.end()).map(|i| format!("arg{i}?")));
+        }
+        Some((parameters, None))
     }
 
-    async fn symbol(&self, _: WorkspaceSymbolParams) -> Result<Option<Vec<SymbolInformation>>> {
-        file_dbg("symbol", "symbol");
-        Ok(None)
+    // a signature labeled like `name(a, b)`, with the offsets of the
+    // parameters in the label
+    fn signature_information(
+        name: &str,
+        parameters: &[String],
+        documentation: Option<String>,
+    ) -> SignatureInformation {
+        let utf16_len = |s: &str| u32::try_from(s.encode_utf16().count()).unwrap_or(u32::MAX);
+        let mut label = format!("{name}(");
+        let mut parameter_information = Vec::new();
+        for (i, parameter) in parameters.iter().enumerate() {
+            if i > 0 {
+                label.push_str(", ");
+            }
+            let start = utf16_len(&label);
+            label.push_str(parameter);
+            parameter_information.push(ParameterInformation {
+                label: ParameterLabel::LabelOffsets([start, utf16_len(&label)]),
+                documentation: None,
+            });
+        }
+        label.push(')');
+        SignatureInformation {
+            label,
+            documentation: documentation.map(|doc| {
+                Documentation::MarkupContent(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: doc,
+                })
+            }),
+            parameters: Some(parameter_information),
+            active_parameter: None,
+        }
     }
 
-    async fn document_highlight(
+    fn get_hover_content(
         &self,
-        _: DocumentHighlightParams,
-    ) -> Result<Option<Vec<DocumentHighlight>>> {
-        file_dbg("document_highlight", "document_highlight");
-        Ok(None)
+        language_name: &str,
+        uri: &Url,
+        doc: &DocumentState,
+        position: Position,
+        folders: &[PathBuf],
+    ) -> Option<Hover> {
+        let language = self.language(language_name);
+        let text = &doc.text;
+        let offset = lsp_utils::to_offset(text, position);
+        let mut sections = Self::get_problem_descriptions(&doc.diagnostics, position);
+        let mut documentation = None;
+        let mut range = None;
+        // TODO merge the repeated tokenize operation with get_completions()?
+        if let Ok((aid, tokens)) = language.tokenize(uri, text) {
+            if let Some((token, extent)) = lsp_utils::get_token(text, &tokens, position) {
+                file_dbg("get_hover_content_token", &token);
+                range = Some(lsp_utils::to_lsp_range(text, &extent));
+                documentation = language
+                    .function_doc(uri, &token)
+                    .map(|function_doc| {
+                        file_dbg("get_hover_content_function_doc", &function_doc.description);
+                        function_doc.to_string()
+                    })
+                    .or_else(|| {
+                        let i = lsp_utils::token_index(&tokens, offset)?;
+                        snippets::keyword_doc(&tokens[i].value).map(ToString::to_string)
+                    });
+            }
+            drop(tokens);
+            unsafe { Arena::delte_index_this_is_really_unsafe_dont_use_it(aid).unwrap() }
+        }
+        sections.extend(
+            documentation.or_else(|| {
+                self.get_symbol_documentation(language_name, uri, text, offset, folders)
+            }),
+        );
+        (!sections.is_empty()).then(|| Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: sections.join("\n\n---\n\n"),
+            }),
+            range,
+        })
     }
 
-    async fn execute_command(&self, _: ExecuteCommandParams) -> Result<Option<Value>> {
-        file_dbg("execute", "execute");
-        self.client
-            .log_message(MessageType::INFO, "executing command!")
-            .await;
-        Ok(None)
+    // descriptions of the `diagnostics` at `position`, with their hint on a
+    // line of its own
+    fn get_problem_descriptions(diagnostics: &[Diagnostic], position: Position) -> Vec<String> {
+        diagnostics
+            .iter()
+            .filter(|d| {
+                let Range { start, end } = d.range;
+                start <= position && (position < end || start == end)
+            })
+            .map(|d| {
+                let level = match d.severity {
+                    Some(DiagnosticSeverity::ERROR) => "error",
+                    Some(DiagnosticSeverity::HINT) => "hint",
+                    _ => "warning",
+                };
+                let code = match &d.code {
+                    Some(NumberOrString::String(code)) => code.clone(),
+                    Some(NumberOrString::Number(code)) => code.to_string(),
+                    None => String::new(),
+                };
+                let message = match d.message.split_once(", Note: ") {
+                    Some((message, hint)) => format!("{message}\n\nNote: {hint}"),
+                    None => d.message.clone(),
+                };
+                format!("**{level}** `{code}`: {message}")
+            })
+            .collect()
     }
 
-    // backend state updates on text edits and reporting of diagnostics
-
-    async fn did_open(&self, params: DidOpenTextDocumentParams) {
-        file_dbg("didOpen", "didOpen");
-        file_dbg("didOpen_language", &params.text_document.language_id);
+    // documentation for the local, const or definition at `offset`
+    fn get_symbol_documentation(
+        &self,
+        language_name: &str,
+        uri: &Url,
+        text: &str,
+        offset: usize,
+        folders: &[PathBuf],
+    ) -> Option<String> {
+        let Target::Symbol(definition) = self.resolve(language_name, uri, text, offset, folders)?
+        else {
+            return None;
+        };
+        // assignments further down the document don't apply yet
+        let (language, before) = if definition.uri == *uri {
+            (self.language(language_name), offset + 1)
+        } else {
+            (
+                self.language(language::detect("", &definition.uri)?),
+                usize::MAX,
+            )
+        };
+        Self::get_symbol_hover(language, &definition, before)
+    }
 
-        let uri = params.text_document.uri;
-        if let Ok(path) = uri.to_file_path() {
-            // TODO pull this from params.text_document.text
-            // TODO cleanup
-            if let Ok(text) = fs::read_to_string(path) {
-                self.update(uri.clone(), &text).await;
-                let d = self.get_diagnostics(&uri, &text);
-                self.client.publish_diagnostics(uri, d, None).await;
+    // the assignment to a local or const in effect `before` an offset, along
+    // with the literal value it has, or the `define` block of a window,
+    // operator, script, pipeline, connector or flow
+    fn get_symbol_hover(
+        language: &dyn language::Language,
+        definition: &SymbolDefinition,
+        before: usize,
+    ) -> Option<String> {
+        let SymbolDefinition {
+            text,
+            symbols,
+            symbol,
+            ..
+        } = definition;
+        match symbols.symbols()[*symbol].kind {
+            language::Kind::Local | language::Kind::Const => {
+                let assignment = Self::assignment(symbols, *symbol, before)?;
+                let line_start = text[..assignment].rfind('\n').map_or(0, |i| i + 1);
+                let line_end = text[assignment..]
+                    .find('\n')
+                    .map_or(text.len(), |i| assignment + i);
+                let mut hover = format!("```tremor\n{}\n```", text[line_start..line_end].trim());
+                if let Some(value) = Self::literal_value(language, definition, assignment) {
+                    hover.push_str(&format!("\n\nvalue: `{value}`"));
+                }
+                Some(hover)
+            }
+            language::Kind::Window
+            | language::Kind::Operator
+            | language::Kind::Script
+            | language::Kind::Pipeline
+            | language::Kind::Connector
+            | language::Kind::Flow => {
+                let block = &text[symbols.symbols()[*symbol].range.clone()];
+                let mut lines: Vec<&str> = block.lines().take(MAX_HOVER_LINES + 1).collect();
+                if lines.len() > MAX_HOVER_LINES {
+                    lines[MAX_HOVER_LINES] = "...";
+                }
+                Some(format!("```tremor\n{}\n```", lines.join("\n")))
             }
+            _ => None,
         }
     }
 
-    async fn did_change(&self, params: DidChangeTextDocumentParams) {
-        file_dbg("didChange", "didChange");
-        // TODO cleanup
-        let uri = params.text_document.uri;
-        let text = &params.content_changes[0].text;
-        self.update(uri.clone(), text).await;
-        self.client
-            .publish_diagnostics(uri.clone(), self.get_diagnostics(&uri, text), None)
-            .await;
+    // the start of the last assignment to `symbol` before `offset`, which is
+    // its definition unless it is rebound
+    fn assignment(symbols: &language::SymbolTable, symbol: usize, offset: usize) -> Option<usize> {
+        symbols
+            .accesses(symbol)
+            .filter(|(range, write)| *write && range.start < offset)
+            .map(|(range, _)| range.start)
+            .max()
     }
 
-    async fn did_close(&self, params: DidCloseTextDocumentParams) {
-        file_dbg("didClose", "didClose");
-        // TODO can cleanup backend state here
-        self.client
-            .publish_diagnostics(params.text_document.uri, vec![], None)
-            .await;
+    // the literal value assigned to the name at `offset`, following names
+    // assigned other names
+    fn literal_value(
+        language: &dyn language::Language,
+        definition: &SymbolDefinition,
+        mut offset: usize,
+    ) -> Option<String> {
+        let (aid, tokens) = language.tokenize(&definition.uri, &definition.text).ok()?;
+        let mut value = None;
+        for _ in 0..MAX_INDIRECTIONS {
+            match language::assigned(&tokens, offset) {
+                Some(language::Assigned::Literal(range)) => {
+                    value = Some(definition.text[range].to_string());
+                    break;
+                }
+                Some(language::Assigned::Name(range)) => {
+                    let Some(assignment) = definition
+                        .symbols
+                        .symbol_at(range.start)
+                        .and_then(|symbol| Self::assignment(&definition.symbols, symbol, offset))
+                    else {
+                        break;
+                    };
+                    offset = assignment;
+                }
+                None => break,
+            }
+        }
+        drop(tokens);
+        unsafe { Arena::delte_index_this_is_really_unsafe_dont_use_it(aid).unwrap() };
+        value
     }
 
-    // other lsp features
-    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
-        file_dbg("completion", "completion");
-
-        // TODO remove unwraps
+    // a copy of an open document, so requests on it are worked out without
+    // holding the state lock, as edits to other documents would wait on them
+    async fn snapshot(&self, uri: &Url) -> Option<DocumentState> {
         let state = self.state.lock().await;
-        let doc = state
-            .get(&params.text_document_position.text_document.uri)
-            .unwrap();
-        let uri = params.text_document_position.text_document.uri;
-
-        Ok(Some(CompletionResponse::Array(self.get_completions(
-            &uri,
-            &doc.text,
-            params.text_document_position.position,
-        ))))
+        let doc = state.get(uri)?;
+        Some(DocumentState {
+            text: doc.text.clone(),
+            version: doc.version,
+            language: doc.language,
+            semantic_tokens: None,
+            diagnostics: doc.diagnostics.clone(),
+        })
     }
 
-    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
-        file_dbg("hover", "hover");
-        // TODO remove unwraps
-        // TODO bake state lookup in self
+    // the text and language of all the open documents, copied out of the state
+    // for requests spanning several documents
+    async fn documents(&self) -> Documents {
         let state = self.state.lock().await;
-        let uri = params.text_document_position_params.text_document.uri;
-        let doc = state.get(&uri).unwrap();
+        state
+            .iter()
+            .map(|(uri, doc)| (uri.clone(), (doc.language, doc.text.clone())))
+            .collect()
+    }
 
-        let result = self
-            .get_hover_content(
-                &uri,
+    // the text and language of a document, from the open `documents` or else
+    // from disk
+    fn document(documents: &Documents, uri: &Url) -> Option<(&'static str, String)> {
+        documents.get(uri).cloned().or_else(|| Self::read(uri))
+    }
+
+    // the text and language of a file on disk
+    fn read(uri: &Url) -> Option<(&'static str, String)> {
+        let text = std::fs::read_to_string(uri.to_file_path().ok()?).ok()?;
+        Some((language::detect("", uri)?, text))
+    }
+
+    // resolves what the identifier at `offset` refers to, which may be defined
+    // in another module, in the module path or the workspace `folders`
+    fn resolve(
+        &self,
+        language: &str,
+        uri: &Url,
+        text: &str,
+        offset: usize,
+        folders: &[PathBuf],
+    ) -> Option<Target> {
+        let symbols = self.language(language).symbols(uri, text)?;
+
+        if let Some(symbol) = symbols.symbol_at(offset) {
+            // on the name of a `use`d module, refer to the module itself
+            if let Some(module) = &symbols.symbols()[symbol].module {
+                if symbols.symbols()[symbol].selection.contains(&offset) {
+                    return Some(Target::Module(module.clone()));
+                }
+            }
+            return Some(Target::Symbol(SymbolDefinition {
+                uri: uri.clone(),
+                text: text.to_string(),
+                symbols,
+                symbol,
+            }));
+        }
+
+        // members of modules, and modules in a module path
+        let (mut module, segment) = symbols.path_at(offset)?;
+        if segment + 1 < module.len() {
+            module.truncate(segment + 1);
+            return Some(Target::Module(module));
+        }
+        let member = module.pop()?;
+        let file = workspace::resolve_module(&module, &self.module_path, folders)?;
+        let text = std::fs::read_to_string(&file).ok()?;
+        let uri = Url::from_file_path(file).ok()?;
+        let symbols = self
+            .language(language::detect("", &uri)?)
+            .symbols(&uri, &text)?;
+        let symbol = symbols.top_level(&member)?;
+        Some(Target::Symbol(SymbolDefinition {
+            uri,
+            text,
+            symbols,
+            symbol,
+        }))
+    }
+
+    fn get_definition(
+        &self,
+        language: &str,
+        uri: &Url,
+        text: &str,
+        position: Position,
+        folders: &[PathBuf],
+    ) -> Option<Location> {
+        let offset = lsp_utils::to_offset(text, position);
+        match self.resolve(language, uri, text, offset, folders)? {
+            Target::Module(module) => {
+                let uri = Url::from_file_path(workspace::resolve_module(
+                    &module,
+                    &self.module_path,
+                    folders,
+                )?)
+                .ok()?;
+                Some(Location::new(uri, Range::default()))
+            }
+            Target::Symbol(definition) => Some(Location::new(
+                definition.uri,
+                lsp_utils::to_lsp_range(
+                    &definition.text,
+                    &definition.symbols.symbols()[definition.symbol].selection,
+                ),
+            )),
+        }
+    }
+
+    async fn get_references(
+        &self,
+        documents: &Documents,
+        folders: &[PathBuf],
+        uri: &Url,
+        position: Position,
+        include_declaration: bool,
+    ) -> Option<Vec<Location>> {
+        let (language, text) = Self::document(documents, uri)?;
+        let offset = lsp_utils::to_offset(&text, position);
+        let Target::Symbol(definition) = self.resolve(language, uri, &text, offset, folders)?
+        else {
+            return None;
+        };
+
+        let mut locations: Vec<Location> = definition
+            .symbols
+            .occurrences(definition.symbol)
+            .skip(usize::from(!include_declaration))
+            .map(|range| {
+                Location::new(
+                    definition.uri.clone(),
+                    lsp_utils::to_lsp_range(&definition.text, range),
+                )
+            })
+            .collect();
+
+        // symbols at the top level of a module can be used from other files
+        if definition.symbols.is_exported(definition.symbol) {
+            let Some(module) = definition
+                .uri
+                .to_file_path()
+                .ok()
+                .and_then(|file| workspace::module_of(&file, &self.module_path, folders))
+            else {
+                return Some(locations);
+            };
+            let name = &definition.symbols.symbols()[definition.symbol].name;
+            // only the files using the module can refer to its symbols, the
+            // open ones may not be in the index if they are outside of it
+            let dependents = self.index.lock().await.dependents(&definition.uri);
+            let mut uris: Vec<Url> = documents.keys().cloned().chain(dependents).collect();
+            uris.sort();
+            uris.dedup();
+            for uri in uris.iter().filter(|uri| **uri != definition.uri) {
+                let Some((language, text)) = Self::document(documents, uri) else {
+                    continue;
+                };
+                let Some(symbols) = self.language(language).symbols(uri, &text) else {
+                    continue;
+                };
+                for range in symbols.member_references(&module, name) {
+                    locations.push(Location::new(
+                        uri.clone(),
+                        lsp_utils::to_lsp_range(&text, &range),
+                    ));
+                }
+            }
+        }
+
+        Some(locations)
+    }
+
+    fn prepare_rename(
+        &self,
+        language: &str,
+        uri: &Url,
+        text: &str,
+        position: Position,
+        folders: &[PathBuf],
+    ) -> Result<Option<PrepareRenameResponse>> {
+        let offset = lsp_utils::to_offset(text, position);
+        let Some(Target::Symbol(definition)) = self.resolve(language, uri, text, offset, folders)
+        else {
+            return Ok(None);
+        };
+        definition
+            .symbols
+            .check_renamable(definition.symbol)
+            .map_err(Error::invalid_params)?;
+        let range = self
+            .language(language)
+            .symbols(uri, text)
+            .and_then(|symbols| symbols.name_at(offset));
+        Ok(
+            range.map(|range| PrepareRenameResponse::RangeWithPlaceholder {
+                range: lsp_utils::to_lsp_range(text, &range),
+                placeholder: text[range].to_string(),
+            }),
+        )
+    }
+
+    async fn get_rename_edit(
+        &self,
+        documents: &Documents,
+        folders: &[PathBuf],
+        position: &TextDocumentPositionParams,
+        new_name: &str,
+    ) -> Result<Option<WorkspaceEdit>> {
+        let uri = &position.text_document.uri;
+        let Some((language, text)) = Self::document(documents, uri) else {
+            return Ok(None);
+        };
+        let offset = lsp_utils::to_offset(&text, position.position);
+        let Some(Target::Symbol(definition)) = self.resolve(language, uri, &text, offset, folders)
+        else {
+            return Ok(None);
+        };
+        definition
+            .symbols
+            .check_rename(definition.symbol, new_name)
+            .map_err(Error::invalid_params)?;
+
+        let mut changes: std::collections::HashMap<Url, Vec<TextEdit>> =
+            std::collections::HashMap::new();
+        for location in self
+            .get_references(documents, folders, uri, position.position, true)
+            .await
+            .unwrap_or_default()
+        {
+            changes
+                .entry(location.uri)
+                .or_default()
+                .push(TextEdit::new(location.range, new_name.to_string()));
+        }
+
+        // the other files refer to the symbol through its module, and would
+        // clash if they already refer to a member of it by the new name
+        let module = definition
+            .uri
+            .to_file_path()
+            .ok()
+            .and_then(|file| workspace::module_of(&file, &self.module_path, folders));
+        for uri in changes.keys().filter(|uri| **uri != definition.uri) {
+            let (Some(module), Some((language, text))) = (&module, Self::document(documents, uri))
+            else {
+                continue;
+            };
+            let Some(symbols) = self.language(language).symbols(uri, &text) else {
+                continue;
+            };
+            if let Some(range) = symbols.member_references(module, new_name).first() {
+                let position = lsp_utils::to_lsp_range(&text, range).start;
+                return Err(Error::invalid_params(format!(
+                    "`{new_name}` would clash with the reference to it at {uri}:{}:{}",
+                    position.line + 1,
+                    position.character + 1
+                )));
+            }
+        }
+        Ok(Some(WorkspaceEdit::new(changes)))
+    }
+
+    // a misspelled registry function, or a module that isn't `use`d yet
+    #[allow(clippy::too_many_arguments)]
+    fn get_function_fixes(
+        &self,
+        language: &dyn language::Language,
+        uri: &Url,
+        text: &str,
+        tokens: &[language::TokenSpan],
+        problem: &language::Problem,
+        module: &str,
+        name: &str,
+    ) -> Vec<(String, TextEdit)> {
+        let functions = language.functions(uri, module);
+        if let Some(suggestion) = quick_fix::suggestion(name, &functions) {
+            return quick_fix::rename_function(text, problem, module, name, suggestion)
+                .map(|edit| (format!("Change to `{module}::{suggestion}`"), edit))
+                .into_iter()
+                .collect();
+        }
+        if !functions.is_empty() || module.contains("::") {
+            return vec![];
+        }
+        let used = language.symbols(uri, text).map_or(false, |symbols| {
+            symbols
+                .symbols()
+                .iter()
+                .any(|s| s.kind == language::Kind::Module && s.name == module)
+        });
+        if used {
+            return vec![];
+        }
+        let (at, indentation) =
+            language::use_insertion(text, tokens, problem.error.start().absolute());
+        let position = lsp_utils::to_position(text, at);
+        workspace::files(&workspace::module_folders(&self.module_path))
+            .iter()
+            .filter_map(|file| workspace::module_of(file, &self.module_path, &[]))
+            .filter(|path| path.last().map(String::as_str) == Some(module))
+            .map(|path| {
+                let statement = format!("use {};", path.join("::"));
+                (
+                    format!("Add `{statement}`"),
+                    TextEdit::new(
+                        Range::new(position, position),
+                        format!("{indentation}{statement}\n"),
+                    ),
+                )
+            })
+            .collect()
+    }
+
+    async fn get_code_actions(
+        &self,
+        documents: &Documents,
+        folders: &[PathBuf],
+        uri: &Url,
+        range: Range,
+        diagnostics: &[Diagnostic],
+    ) -> Vec<CodeActionOrCommand> {
+        let Some((language_name, text)) = Self::document(documents, uri) else {
+            return vec![];
+        };
+        let language = self.language(language_name);
+        let Ok((aid, tokens)) = language.tokenize(uri, &text) else {
+            return vec![];
+        };
+        // renames are only worked out once the tokens are dropped, as they
+        // look up the files using the document in the index
+        let mut fixes: Vec<(String, Fix, Range)> = Vec::new();
+        let mut fix = |title: String, edit: Fix, problem_range: Range| {
+            fixes.push((title, edit, problem_range));
+        };
+        let single = |edit: TextEdit| {
+            WorkspaceEdit::new(std::iter::once((uri.clone(), vec![edit])).collect())
+        };
+
+        for problem in language.parse_errors(uri, &text) {
+            let problem_range = Range {
+                start: lsp_utils::to_lsp_position(&problem.error.start()),
+                end: lsp_utils::to_lsp_position(&problem.error.end()),
+            };
+            if problem_range.end < range.start || range.end < problem_range.start {
+                continue;
+            }
+
+            if let Some((module, name)) = quick_fix::undefined_function(&problem) {
+                for (title, edit) in
+                    self.get_function_fixes(language, uri, &text, &tokens, &problem, module, name)
+                {
+                    fix(title, Fix::Edit(single(edit)), problem_range);
+                }
+            } else if quick_fix::is_lower_case_const(&problem) {
+                let start = problem.error.start().absolute();
+                let name = language.symbols(uri, &text).and_then(|symbols| {
+                    symbols
+                        .symbols()
+                        .iter()
+                        .find(|s| s.kind == language::Kind::Const && s.selection.start >= start)
+                        .map(|s| (s.name.clone(), s.selection.start))
+                });
+                if let Some((name, offset)) = name {
+                    let upper = quick_fix::upper_case(&name);
+                    let position = TextDocumentPositionParams::new(
+                        TextDocumentIdentifier::new(uri.clone()),
+                        lsp_utils::to_position(&text, offset),
+                    );
+                    fix(
+                        format!("Rename to `{upper}`"),
+                        Fix::Rename(position, upper),
+                        problem_range,
+                    );
+                }
+            } else {
+                for (title, edit) in quick_fix::terminators(&text, &tokens, &problem) {
+                    fix(title, Fix::Edit(single(edit)), problem_range);
+                }
+            }
+        }
+        drop(tokens);
+        unsafe { Arena::delte_index_this_is_really_unsafe_dont_use_it(aid).unwrap() };
+
+        let mut actions = Vec::new();
+        for (title, edit, problem_range) in fixes {
+            let edit = match edit {
+                Fix::Edit(edit) => edit,
+                Fix::Rename(position, new_name) => {
+                    match self
+                        .get_rename_edit(documents, folders, &position, &new_name)
+                        .await
+                    {
+                        Ok(Some(edit)) => edit,
+                        _ => continue,
+                    }
+                }
+            };
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title,
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: Some(
+                    diagnostics
+                        .iter()
+                        .filter(|d| d.range == problem_range)
+                        .cloned()
+                        .collect(),
+                ),
+                edit: Some(edit),
+                ..CodeAction::default()
+            }));
+        }
+        actions
+    }
+
+    fn get_document_highlights(
+        &self,
+        language: &str,
+        uri: &Url,
+        text: &str,
+        position: Position,
+    ) -> Option<Vec<DocumentHighlight>> {
+        let symbols = self.language(language).symbols(uri, text)?;
+        let symbol = symbols.symbol_at(lsp_utils::to_offset(text, position))?;
+        let highlights = symbols
+            .accesses(symbol)
+            .map(|(range, write)| DocumentHighlight {
+                range: lsp_utils::to_lsp_range(text, range),
+                kind: Some(if write {
+                    DocumentHighlightKind::WRITE
+                } else {
+                    DocumentHighlightKind::READ
+                }),
+            })
+            .collect();
+        Some(highlights)
+    }
+
+    fn get_semantic_tokens(
+        &self,
+        language: &str,
+        uri: &Url,
+        text: &str,
+        range: Option<Range>,
+    ) -> Option<Vec<SemanticToken>> {
+        let language = self.language(language);
+        let symbols = language.symbols(uri, text);
+        let (aid, tokens) = language.tokenize(uri, text).ok()?;
+        let range =
+            range.map(|r| lsp_utils::to_offset(text, r.start)..lsp_utils::to_offset(text, r.end));
+        let encoded = semantic_tokens::encode(text, &tokens, symbols.as_ref(), range.as_ref());
+        drop(tokens);
+        unsafe { Arena::delte_index_this_is_really_unsafe_dont_use_it(aid).unwrap() };
+        Some(encoded)
+    }
+
+    fn get_document_symbols(
+        &self,
+        language: &str,
+        uri: &Url,
+        text: &str,
+    ) -> Option<Vec<DocumentSymbol>> {
+        let symbols = self.language(language).symbols(uri, text)?;
+        Some(document_symbols(&symbols, &symbols.outline(), text))
+    }
+
+    // edits formatting the `lines` of a document, indenting by the configured
+    // width or else as the client asks
+    fn get_formatting(
+        &self,
+        language: &str,
+        uri: &Url,
+        text: &str,
+        options: &FormattingOptions,
+        lines: RangeInclusive<usize>,
+    ) -> Option<Vec<TextEdit>> {
+        let unit = self.indent_unit(options)?;
+        let (aid, tokens) = self.language(language).tokenize(uri, text).ok()?;
+        let edits = formatter::edits(text, &tokens, &unit, lines);
+        drop(tokens);
+        unsafe { Arena::delte_index_this_is_really_unsafe_dont_use_it(aid).unwrap() };
+        Some(edits)
+    }
+
+    // edits formatting the line of `position`, once `ch` is typed there and
+    // completes an `end` or `;`, rather than a word, string or comment
+    fn get_on_type_formatting(
+        &self,
+        language: &str,
+        uri: &Url,
+        text: &str,
+        options: &FormattingOptions,
+        position: Position,
+        ch: &str,
+    ) -> Option<Vec<TextEdit>> {
+        let unit = self.indent_unit(options)?;
+        let (aid, tokens) = self.language(language).tokenize(uri, text).ok()?;
+        let offset = lsp_utils::to_offset(text, position);
+        let typed = offset
+            .checked_sub(1)
+            .and_then(|end| lsp_utils::token_index(&tokens, end))
+            .map(|i| &tokens[i]);
+        let completed = if ch == "d" {
+            language::Token::End
+        } else {
+            language::Token::Semi
+        };
+        let edits = match typed {
+            Some(token) if token.value == completed && token.span.end().absolute() == offset => {
+                let line = position.line as usize;
+                Some(formatter::edits(text, &tokens, &unit, line..=line))
+            }
+            _ => None,
+        };
+        drop(tokens);
+        unsafe { Arena::delte_index_this_is_really_unsafe_dont_use_it(aid).unwrap() };
+        edits
+    }
+
+    // one level of indentation: the configured width or else as the client
+    // asks
+    fn indent_unit(&self, options: &FormattingOptions) -> Option<String> {
+        Some(match self.indent_width.load(Ordering::Relaxed) {
+            0 if !options.insert_spaces => "\t".to_string(),
+            0 => " ".repeat(usize::try_from(options.tab_size).ok()?),
+            width => " ".repeat(usize::try_from(width).ok()?),
+        })
+    }
+
+    // (re-)indexes a document, from the client's version if it is open or else
+    // from disk. files that are gone, or were opened from outside of the
+    // indexed folders, are dropped from the index.
+    async fn index(&self, uri: &Url) {
+        let (document, open) = match self.snapshot(uri).await {
+            Some(doc) => (Some((doc.language, doc.text)), true),
+            None => (Self::read(uri), false),
+        };
+        let indexed = match uri.to_file_path() {
+            Ok(file) if !open => {
+                let mut folders = self.folders().await;
+                folders.extend(workspace::module_folders(&self.module_path));
+                folders.iter().any(|folder| file.starts_with(folder))
+            }
+            Ok(_) => true,
+            Err(()) => false,
+        };
+        let folders = self.folders().await;
+        let symbols = document.filter(|_| indexed).and_then(|(language, text)| {
+            let symbols = self.language(language).symbols(uri, &text)?;
+            Some((
+                workspace::index_symbols(uri, &text, &symbols),
+                workspace::index_uses(&symbols, &self.module_path, &folders),
+            ))
+        });
+        let mut index = self.index.lock().await;
+        match symbols {
+            Some((symbols, uses)) => index.update(uri.clone(), symbols, uses),
+            None => index.remove(uri),
+        }
+    }
+
+    // indexes all tremor files in `folders` in the background, reading and
+    // parsing them on a blocking thread. open documents are left out, as their
+    // edits are indexed as they are made.
+    fn index_folders(&self, folders: Vec<PathBuf>) {
+        let client = self.client.clone();
+        let languages = self.languages.clone();
+        let state = self.state.clone();
+        let index = self.index.clone();
+        let workspace_folders = self.workspace_folders.clone();
+        let module_path = self.module_path.clone();
+        async_std::task::spawn(async move {
+            let uses_folders = Self::paths(&workspace_folders.lock().await);
+            let indexed = async_std::task::spawn_blocking(move || {
+                let mut indexed = Vec::new();
+                for file in workspace::files(&folders) {
+                    let Ok(uri) = Url::from_file_path(file) else {
+                        continue;
+                    };
+                    let Some((language, text)) = Self::read(&uri) else {
+                        continue;
+                    };
+                    let Some(symbols) = languages
+                        .get(language)
+                        .and_then(|language| language.symbols(&uri, &text))
+                    else {
+                        continue;
+                    };
+                    let uses = workspace::index_uses(&symbols, &module_path, &uses_folders);
+                    let symbols = workspace::index_symbols(&uri, &text, &symbols);
+                    indexed.push((uri, symbols, uses));
+                }
+                indexed
+            })
+            .await;
+            let count = indexed.len();
+            let open: Vec<Url> = state.lock().await.keys().cloned().collect();
+            let mut index = index.lock().await;
+            for (uri, symbols, uses) in indexed {
+                if !open.contains(&uri) {
+                    index.update(uri, symbols, uses);
+                }
+            }
+            drop(index);
+            client
+                .log_message(MessageType::INFO, format!("Indexed {count} tremor files"))
+                .await;
+        });
+    }
+
+    fn get_workspace_symbols(index: &workspace::Index, query: &str) -> Vec<SymbolInformation> {
+        index
+            .search(query)
+            .into_iter()
+            .map(|symbol| {
+                #[allow(deprecated)] // `SymbolInformation::deprecated` has to be set
+                SymbolInformation {
+                    name: symbol.name.clone(),
+                    kind: lsp_utils::to_symbol_kind(symbol.kind),
+                    tags: None,
+                    deprecated: None,
+                    location: symbol.location.clone(),
+                    container_name: symbol.container.clone(),
+                }
+            })
+            .collect()
+    }
+
+    // the workspace folders, as paths
+    async fn folders(&self) -> Vec<PathBuf> {
+        Self::paths(&self.workspace_folders.lock().await)
+    }
+
+    fn paths(folders: &[Url]) -> Vec<PathBuf> {
+        folders
+            .iter()
+            .filter_map(|uri| uri.to_file_path().ok())
+            .collect()
+    }
+}
+
+#[allow(deprecated)] // `DocumentSymbol::deprecated` has to be set
+fn document_symbols(
+    symbols: &language::SymbolTable,
+    outline: &[language::Outline],
+    text: &str,
+) -> Vec<DocumentSymbol> {
+    outline
+        .iter()
+        .map(|o| {
+            let symbol = &symbols.symbols()[o.symbol];
+            DocumentSymbol {
+                name: symbol.name.clone(),
+                detail: symbol.detail.clone(),
+                kind: lsp_utils::to_symbol_kind(symbol.kind),
+                tags: None,
+                deprecated: None,
+                range: lsp_utils::to_lsp_range(text, &symbol.range),
+                selection_range: lsp_utils::to_lsp_range(text, &symbol.selection),
+                children: Some(document_symbols(symbols, &o.children, text))
+                    .filter(|children| !children.is_empty()),
+            }
+        })
+        .collect()
+}
+
+#[tower_lsp::async_trait]
+impl LanguageServer for Backend {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+        let folders = match (params.workspace_folders, params.root_uri) {
+            (Some(folders), _) => folders.into_iter().map(|folder| folder.uri).collect(),
+            (None, Some(root)) => vec![root],
+            (None, None) => vec![],
+        };
+        *self.workspace_folders.lock().await = folders;
+        let watch_files = params
+            .capabilities
+            .workspace
+            .and_then(|workspace| workspace.did_change_watched_files)
+            .and_then(|watched_files| watched_files.dynamic_registration)
+            .unwrap_or(false);
+        self.watch_files.store(watch_files, Ordering::Relaxed);
+        if let Some(options) = &params.initialization_options {
+            self.configure(options);
+        }
+
+        Ok(InitializeResult {
+            server_info: Some(ServerInfo {
+                name: "tremor-language-server".to_string(),
+                version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            }),
+            capabilities: ServerCapabilities {
+                completion_provider: Some(CompletionOptions {
+                    trigger_characters: Some(vec![":".to_string()]),
+                    work_done_progress_options: WorkDoneProgressOptions::default(),
+                    ..CompletionOptions::default()
+                }),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                signature_help_provider: Some(SignatureHelpOptions {
+                    trigger_characters: Some(vec!["(".to_string(), ",".to_string()]),
+                    retrigger_characters: None,
+                    work_done_progress_options: WorkDoneProgressOptions::default(),
+                }),
+                code_action_provider: Some(CodeActionProviderCapability::Options(
+                    CodeActionOptions {
+                        code_action_kinds: Some(vec![CodeActionKind::QUICKFIX]),
+                        work_done_progress_options: WorkDoneProgressOptions::default(),
+                        resolve_provider: None,
+                    },
+                )),
+                definition_provider: Some(OneOf::Left(true)),
+                references_provider: Some(OneOf::Left(true)),
+                document_highlight_provider: Some(OneOf::Left(true)),
+                document_symbol_provider: Some(OneOf::Left(true)),
+                semantic_tokens_provider: Some(
+                    SemanticTokensServerCapabilities::SemanticTokensOptions(
+                        SemanticTokensOptions {
+                            work_done_progress_options: WorkDoneProgressOptions::default(),
+                            legend: semantic_tokens::legend(),
+                            range: Some(true),
+                            full: Some(SemanticTokensFullOptions::Delta { delta: Some(true) }),
+                        },
+                    ),
+                ),
+                workspace_symbol_provider: Some(OneOf::Left(true)),
+                document_formatting_provider: Some(OneOf::Left(true)),
+                document_range_formatting_provider: Some(OneOf::Left(true)),
+                document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+                    first_trigger_character: ";".to_string(),
+                    // the last letter of `end`
+                    more_trigger_character: Some(vec!["d".to_string()]),
+                }),
+                rename_provider: Some(OneOf::Right(RenameOptions {
+                    prepare_provider: Some(true),
+                    work_done_progress_options: WorkDoneProgressOptions::default(),
+                })),
+                text_document_sync: Some(TextDocumentSyncCapability::Options(
+                    TextDocumentSyncOptions {
+                        open_close: Some(true),
+                        change: Some(TextDocumentSyncKind::INCREMENTAL),
+                        save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+                        ..TextDocumentSyncOptions::default()
+                    },
+                )),
+                workspace: Some(WorkspaceServerCapabilities {
+                    workspace_folders: Some(WorkspaceFoldersServerCapabilities {
+                        supported: Some(true),
+                        change_notifications: Some(OneOf::Left(true)),
+                    }),
+                    ..WorkspaceServerCapabilities::default()
+                }),
+                ..ServerCapabilities::default()
+            },
+        })
+    }
+
+    async fn initialized(&self, _: InitializedParams) {
+        file_dbg("initialized", "initialized");
+
+        let mut folders = self.folders().await;
+        folders.extend(workspace::module_folders(&self.module_path));
+        self.index_folders(folders);
+
+        if self.watch_files.load(Ordering::Relaxed) {
+            let glob_pattern = format!("**/*.{{{}}}", language::FILE_EXTENSIONS.join(","));
+            let options = DidChangeWatchedFilesRegistrationOptions {
+                watchers: vec![FileSystemWatcher {
+                    glob_pattern: GlobPattern::String(glob_pattern),
+                    kind: None,
+                }],
+            };
+            let registration = Registration {
+                id: "tremor-files".to_string(),
+                method: "workspace/didChangeWatchedFiles".to_string(),
+                register_options: serde_json::to_value(options).ok(),
+            };
+            if let Err(e) = self.client.register_capability(vec![registration]).await {
+                self.client
+                    .log_message(
+                        MessageType::WARNING,
+                        format!("Failed to watch tremor files: {e}"),
+                    )
+                    .await;
+            }
+        }
+
+        // lsp-types can't tell whether the client pulls diagnostics, nor
+        // advertise it, so it is registered dynamically. clients that don't
+        // support it refuse, and keep getting them pushed.
+        let client = self.client.clone();
+        async_std::task::spawn(async move {
+            let registration = Registration {
+                id: "tremor-diagnostics".to_string(),
+                method: "textDocument/diagnostic".to_string(),
+                register_options: Some(json!({
+                    "documentSelector": null,
+                    "identifier": "tremor",
+                    "interFileDependencies": true,
+                    "workspaceDiagnostics": true,
+                })),
+            };
+            if let Err(e) = client.register_capability(vec![registration]).await {
+                client
+                    .log_message(
+                        MessageType::INFO,
+                        format!("Diagnostics are pushed, as pulling them is unsupported: {e}"),
+                    )
+                    .await;
+            }
+        });
+
+        // TODO check this from clients
+        //self.client.show_message(MessageType::Info, "Initialized Trill!").await;
+        self.client
+            .log_message(MessageType::INFO, "Initialized Trill!")
+            .await;
+    }
+
+    // TODO do more here (as appropriate). manadatory implementations for the trait
+
+    async fn shutdown(&self) -> Result<()> {
+        file_dbg("shutdown", "shutdown");
+        Ok(())
+    }
+
+    async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
+        file_dbg("didChangeConfiguration", &params.settings.to_string());
+        self.configure(&params.settings["tremor"]);
+    }
+
+    async fn did_change_workspace_folders(&self, params: DidChangeWorkspaceFoldersParams) {
+        file_dbg("didChangeWorkspaceFolders", "didChangeWorkspaceFolders");
+        {
+            let mut folders = self.workspace_folders.lock().await;
+            folders.retain(|uri| !params.event.removed.iter().any(|f| &f.uri == uri));
+            folders.extend(params.event.added.iter().map(|folder| folder.uri.clone()));
+        }
+        {
+            let mut index = self.index.lock().await;
+            for folder in &params.event.removed {
+                if let Ok(path) = folder.uri.to_file_path() {
+                    index.remove_folder(&path);
+                }
+            }
+        }
+        let added: Vec<PathBuf> = params
+            .event
+            .added
+            .iter()
+            .filter_map(|folder| folder.uri.to_file_path().ok())
+            .collect();
+        self.index_folders(added);
+    }
+
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        file_dbg("didChangeWatchedFiles", "didChangeWatchedFiles");
+        for change in params.changes {
+            if change.typ == FileChangeType::DELETED {
+                self.check_dependents(&change.uri).await;
+                self.index.lock().await.remove(&change.uri);
+            } else {
+                self.index(&change.uri).await;
+                self.check_dependents(&change.uri).await;
+            }
+        }
+    }
+
+    async fn symbol(
+        &self,
+        params: WorkspaceSymbolParams,
+    ) -> Result<Option<Vec<SymbolInformation>>> {
+        file_dbg("symbol", "symbol");
+        let index = self.index.lock().await;
+        Ok(Some(Self::get_workspace_symbols(&index, &params.query)))
+    }
+
+    async fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> Result<Option<Vec<DocumentHighlight>>> {
+        file_dbg("document_highlight", "document_highlight");
+        let uri = params.text_document_position_params.text_document.uri;
+        let Some(doc) = self.snapshot(&uri).await else {
+            return Ok(None);
+        };
+
+        Ok(self.get_document_highlights(
+            doc.language,
+            &uri,
+            &doc.text,
+            params.text_document_position_params.position,
+        ))
+    }
+
+    async fn execute_command(&self, _: ExecuteCommandParams) -> Result<Option<Value>> {
+        file_dbg("execute", "execute");
+        self.client
+            .log_message(MessageType::INFO, "executing command!")
+            .await;
+        Ok(None)
+    }
+
+    // backend state updates on text edits and reporting of diagnostics
+
+    async fn did_open(&self, params: DidOpenTextDocumentParams) {
+        file_dbg("didOpen", "didOpen");
+        file_dbg("didOpen_language", &params.text_document.language_id);
+
+        let doc = params.text_document;
+        let language =
+            language::detect(&doc.language_id, &doc.uri).unwrap_or(self.default_language);
+        self.update(doc.uri.clone(), &doc.text, doc.version, language)
+            .await;
+        self.index(&doc.uri).await;
+        self.analyze(
+            doc.uri,
+            language,
+            doc.text,
+            doc.version,
+            Duration::ZERO,
+            true,
+        );
+    }
+
+    async fn did_change(&self, params: DidChangeTextDocumentParams) {
+        file_dbg("didChange", "didChange");
+        if let Some((language, text)) = self.apply_changes(&params).await {
+            let uri = params.text_document.uri;
+            let delay = Duration::from_millis(self.debounce.load(Ordering::Relaxed));
+            self.analyze(
+                uri,
+                language,
+                text,
+                params.text_document.version,
+                delay,
+                false,
+            );
+        } else {
+            self.client
+                .log_message(
+                    MessageType::WARNING,
+                    format!(
+                        "Ignoring stale or unknown change to {} (version {})",
+                        params.text_document.uri, params.text_document.version
+                    ),
+                )
+                .await;
+        }
+    }
+
+    async fn did_save(&self, params: DidSaveTextDocumentParams) {
+        file_dbg("didSave", "didSave");
+        self.check_dependents(&params.text_document.uri).await;
+    }
+
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        file_dbg("didClose", "didClose");
+        self.state.lock().await.remove(&params.text_document.uri);
+        self.index(&params.text_document.uri).await;
+        self.client
+            .publish_diagnostics(params.text_document.uri, vec![], None)
+            .await;
+    }
+
+    // other lsp features
+    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        file_dbg("completion", "completion");
+
+        let folders = self.folders().await;
+        let uri = params.text_document_position.text_document.uri;
+        let Some(doc) = self.snapshot(&uri).await else {
+            return Ok(None);
+        };
+
+        Ok(Some(CompletionResponse::Array(self.get_completions(
+            doc.language,
+            &uri,
+            &doc.text,
+            params.text_document_position.position,
+            &folders,
+        ))))
+    }
+
+    async fn signature_help(&self, params: SignatureHelpParams) -> Result<Option<SignatureHelp>> {
+        file_dbg("signature_help", "signature_help");
+
+        let folders = self.folders().await;
+        let uri = params.text_document_position_params.text_document.uri;
+        let Some(doc) = self.snapshot(&uri).await else {
+            return Ok(None);
+        };
+
+        Ok(self.get_signature_help(
+            doc.language,
+            &uri,
+            &doc.text,
+            params.text_document_position_params.position,
+            &folders,
+        ))
+    }
+
+    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        file_dbg("hover", "hover");
+        let folders = self.folders().await;
+        let uri = params.text_document_position_params.text_document.uri;
+        let Some(doc) = self.snapshot(&uri).await else {
+            return Ok(None);
+        };
+
+        Ok(self.get_hover_content(
+            doc.language,
+            &uri,
+            &doc,
+            params.text_document_position_params.position,
+            &folders,
+        ))
+    }
+
+    async fn goto_definition(
+        &self,
+        params: GotoDefinitionParams,
+    ) -> Result<Option<GotoDefinitionResponse>> {
+        file_dbg("goto_definition", "goto_definition");
+        let folders = self.folders().await;
+        let uri = params.text_document_position_params.text_document.uri;
+        let Some(doc) = self.snapshot(&uri).await else {
+            return Ok(None);
+        };
+
+        Ok(self
+            .get_definition(
+                doc.language,
+                &uri,
                 &doc.text,
                 params.text_document_position_params.position,
+                &folders,
+            )
+            .map(GotoDefinitionResponse::Scalar))
+    }
+
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<PrepareRenameResponse>> {
+        file_dbg("prepare_rename", "prepare_rename");
+        let folders = self.folders().await;
+        let Some(doc) = self.snapshot(&params.text_document.uri).await else {
+            return Ok(None);
+        };
+
+        self.prepare_rename(
+            doc.language,
+            &params.text_document.uri,
+            &doc.text,
+            params.position,
+            &folders,
+        )
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        file_dbg("rename", "rename");
+        let folders = self.folders().await;
+        let documents = self.documents().await;
+        if !documents.contains_key(&params.text_document_position.text_document.uri) {
+            return Ok(None);
+        }
+
+        self.get_rename_edit(
+            &documents,
+            &folders,
+            &params.text_document_position,
+            &params.new_name,
+        )
+        .await
+    }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        file_dbg("code_action", "code_action");
+        let folders = self.folders().await;
+        let documents = self.documents().await;
+        if !documents.contains_key(&params.text_document.uri) {
+            return Ok(None);
+        }
+
+        Ok(Some(
+            self.get_code_actions(
+                &documents,
+                &folders,
+                &params.text_document.uri,
+                params.range,
+                &params.context.diagnostics,
+            )
+            .await,
+        ))
+    }
+
+    async fn document_symbol(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> Result<Option<DocumentSymbolResponse>> {
+        file_dbg("document_symbol", "document_symbol");
+        let uri = params.text_document.uri;
+        let Some(doc) = self.snapshot(&uri).await else {
+            return Ok(None);
+        };
+
+        Ok(self
+            .get_document_symbols(doc.language, &uri, &doc.text)
+            .map(DocumentSymbolResponse::Nested))
+    }
+
+    async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+        file_dbg("references", "references");
+        let folders = self.folders().await;
+        let documents = self.documents().await;
+        let position = params.text_document_position;
+        if !documents.contains_key(&position.text_document.uri) {
+            return Ok(None);
+        }
+
+        Ok(self
+            .get_references(
+                &documents,
+                &folders,
+                &position.text_document.uri,
+                position.position,
+                params.context.include_declaration,
+            )
+            .await)
+    }
+
+    async fn semantic_tokens_full(
+        &self,
+        params: SemanticTokensParams,
+    ) -> Result<Option<SemanticTokensResult>> {
+        file_dbg("semantic_tokens_full", "semantic_tokens_full");
+        let uri = params.text_document.uri;
+        let Some(doc) = self.snapshot(&uri).await else {
+            return Ok(None);
+        };
+        let Some(data) = self.get_semantic_tokens(doc.language, &uri, &doc.text, None) else {
+            return Ok(None);
+        };
+
+        let tokens = SemanticTokens {
+            result_id: Some(doc.version.to_string()),
+            data,
+        };
+        if let Some(doc) = self
+            .state
+            .lock()
+            .await
+            .get_mut(&uri)
+            .filter(|current| current.version == doc.version)
+        {
+            doc.semantic_tokens = Some(tokens.clone());
+        }
+        Ok(Some(SemanticTokensResult::Tokens(tokens)))
+    }
+
+    async fn semantic_tokens_full_delta(
+        &self,
+        params: SemanticTokensDeltaParams,
+    ) -> Result<Option<SemanticTokensFullDeltaResult>> {
+        file_dbg("semantic_tokens_full_delta", "semantic_tokens_full_delta");
+        let uri = params.text_document.uri;
+        let Some(doc) = self.snapshot(&uri).await else {
+            return Ok(None);
+        };
+        let Some(data) = self.get_semantic_tokens(doc.language, &uri, &doc.text, None) else {
+            return Ok(None);
+        };
+
+        let tokens = SemanticTokens {
+            result_id: Some(doc.version.to_string()),
+            data,
+        };
+        // the tokens are only kept if the document didn't change meanwhile
+        let previous = self
+            .state
+            .lock()
+            .await
+            .get_mut(&uri)
+            .filter(|current| current.version == doc.version)
+            .and_then(|current| current.semantic_tokens.replace(tokens.clone()))
+            .filter(|previous| previous.result_id.as_ref() == Some(&params.previous_result_id));
+        Ok(Some(match previous {
+            Some(previous) => SemanticTokensFullDeltaResult::TokensDelta(SemanticTokensDelta {
+                result_id: tokens.result_id,
+                edits: semantic_tokens::edits(&previous.data, &tokens.data),
+            }),
+            None => SemanticTokensFullDeltaResult::Tokens(tokens),
+        }))
+    }
+
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        file_dbg("formatting", "formatting");
+        let uri = params.text_document.uri;
+        let Some(doc) = self.snapshot(&uri).await else {
+            return Ok(None);
+        };
+
+        Ok(self.get_formatting(
+            doc.language,
+            &uri,
+            &doc.text,
+            &params.options,
+            0..=usize::MAX,
+        ))
+    }
+
+    async fn range_formatting(
+        &self,
+        params: DocumentRangeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        file_dbg("range_formatting", "range_formatting");
+        let uri = params.text_document.uri;
+        let Some(doc) = self.snapshot(&uri).await else {
+            return Ok(None);
+        };
+
+        let Range { start, end } = params.range;
+        // a range ending at the start of a line doesn't cover that line
+        let last = if end.character == 0 && end.line > start.line {
+            end.line - 1
+        } else {
+            end.line
+        };
+        Ok(self.get_formatting(
+            doc.language,
+            &uri,
+            &doc.text,
+            &params.options,
+            start.line as usize..=last as usize,
+        ))
+    }
+
+    async fn on_type_formatting(
+        &self,
+        params: DocumentOnTypeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        file_dbg("on_type_formatting", "on_type_formatting");
+        let uri = params.text_document_position.text_document.uri;
+        let Some(doc) = self.snapshot(&uri).await else {
+            return Ok(None);
+        };
+
+        Ok(self.get_on_type_formatting(
+            doc.language,
+            &uri,
+            &doc.text,
+            &params.options,
+            params.text_document_position.position,
+            &params.ch,
+        ))
+    }
+
+    async fn semantic_tokens_range(
+        &self,
+        params: SemanticTokensRangeParams,
+    ) -> Result<Option<SemanticTokensRangeResult>> {
+        file_dbg("semantic_tokens_range", "semantic_tokens_range");
+        let uri = params.text_document.uri;
+        let Some(doc) = self.snapshot(&uri).await else {
+            return Ok(None);
+        };
+
+        Ok(self
+            .get_semantic_tokens(doc.language, &uri, &doc.text, Some(params.range))
+            .map(|data| {
+                SemanticTokensRangeResult::Tokens(SemanticTokens {
+                    result_id: None,
+                    data,
+                })
+            }))
+    }
+}
+
+// TODO remove. just for testing right now
+pub(crate) fn file_dbg(name: &str, content: &str) {
+    use std::env::temp_dir;
+    use std::fs::File;
+    use std::io::Write;
+
+    let mut path = PathBuf::new();
+    path.push(temp_dir());
+    path.push(format!("tremor_{name}"));
+
+    let mut output = File::create(path).unwrap();
+    write!(output, "{content}").unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use async_std::prelude::{FutureExt, StreamExt};
+    use serde_json::json;
+    use tower::Service;
+    use tower_lsp::jsonrpc::{Id, Request};
+    use tower_lsp::LspService;
+
+    use super::*;
+    use std::path::Path;
+    use std::sync::OnceLock;
+
+    const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+    // the modules shared by the tests, written once to a folder that is kept
+    // around until the tests are done
+    fn modules() -> &'static Path {
+        static MODULES: OnceLock<tempfile::TempDir> = OnceLock::new();
+        MODULES
+            .get_or_init(|| {
+                let modules = tempfile::tempdir().unwrap();
+                for (file, text) in [
+                    ("answers/deep.tremor", "const ANSWER = 42;\n"),
+                    (
+                        "answers/helpers.tremor",
+                        "## Adds one\n## to a number\nfn inc(x) with\n  x + 1\nend;\n## The answer\nconst ANSWER = 42;\nfn undocumented() with\n  1\nend;\n",
+                    ),
+                    ("answers/loud.tremor", "fn shout(x) with x end;\n"),
+                    (
+                        "std/string.tremor",
+                        "## Formats a string\nintrinsic fn format(format, ...) as string::format;\n",
+                    ),
+                ] {
+                    let file = modules.path().join(file);
+                    std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+                    std::fs::write(file, text).unwrap();
+                }
+                modules
+            })
+            .path()
+    }
+
+    // the module path of the shared modules
+    fn module_path() -> ModulePath {
+        ModulePath {
+            mounts: vec![modules().to_string_lossy().to_string()],
+        }
+    }
+
+    // the uri of a document named `name` in the folder `dir`
+    fn file_uri(dir: &tempfile::TempDir, name: &str) -> String {
+        Url::from_file_path(dir.path().join(name))
+            .unwrap()
+            .to_string()
+    }
+
+    async fn initialize(service: &mut LspService<Backend>) {
+        initialize_with(service, json!({"capabilities":{}})).await;
+    }
+
+    async fn initialize_with(service: &mut LspService<Backend>, params: Value) {
+        let req = Request::build("initialize").params(params).id(1).finish();
+        service
+            .call(req)
+            .await
+            .expect("Expect request to be executed");
+        let req = Request::build("initialized").params(json!({})).finish();
+        service
+            .call(req)
+            .await
+            .expect("Expect request to be executed");
+    }
+
+    // drains the messages to the client, signalling when the workspace folders
+    // are done being indexed in the background
+    fn indexing(mut socket: tower_lsp::ClientSocket) -> async_std::channel::Receiver<()> {
+        let (sender, indexed) = async_std::channel::unbounded();
+        async_std::task::spawn(async move {
+            while let Some(x) = socket.next().await {
+                let indexed = x.method() == "window/logMessage"
+                    && x.params().map_or(false, |params| {
+                        params["message"]
+                            .as_str()
+                            .map_or(false, |message| message.starts_with("Indexed"))
+                    });
+                if indexed && sender.send(()).await.is_err() {
+                    break;
+                }
+            }
+        });
+        indexed
+    }
+
+    async fn open(service: &mut LspService<Backend>, uri: &str, text: &str) {
+        let req = Request::build("textDocument/didOpen")
+            .params(json!({"textDocument": {
+                "uri": uri,
+                "languageId": "",
+                "version": 1,
+                "text": text
+            }}))
+            .finish();
+        service
+            .call(req)
+            .await
+            .expect("Expect request to be executed");
+    }
+
+    async fn request(
+        service: &mut LspService<Backend>,
+        method: &'static str,
+        params: Value,
+    ) -> Value {
+        let req = Request::build(method).params(params).id(2).finish();
+        service
+            .call(req)
+            .await
+            .expect("Expect request to be executed")
+            .expect("Expect response")
+            .result()
+            .expect("Expect a successful response")
+            .clone()
+    }
+
+    async fn request_error(
+        service: &mut LspService<Backend>,
+        method: &'static str,
+        params: Value,
+    ) -> String {
+        let req = Request::build(method).params(params).id(2).finish();
+        let (_, result) = service
+            .call(req)
+            .await
+            .expect("Expect request to be executed")
+            .expect("Expect response")
+            .into_parts();
+        result
+            .expect_err("Expect an error response")
+            .message
+            .to_string()
+    }
+
+    #[async_std::test]
+    async fn backend() -> Result<()> {
+        let (mut service, _socket) =
+            LspService::new(|client| Backend::new(client, "tremor-deploy", module_path()));
+        let req = Request::build("initialize")
+            .params(json!({"capabilities":{}}))
+            .id(1)
+            .finish();
+        let res = service
+            .call(req)
+            .await
+            .expect("Expect request to be executed")
+            .expect("Expect response");
+        assert_eq!(res.id(), &Id::Number(1));
+        assert!(res.is_ok());
+        assert_eq!(
+            &json!({
+                "capabilities": {
+                    "codeActionProvider": {
+                        "codeActionKinds": ["quickfix"],
+                    },
+                    "completionProvider": {
+                        "triggerCharacters": [":"],
+                    },
+                    "textDocumentSync": {"openClose": true, "change": 2, "save": true},
+                    "workspace": {
+                        "workspaceFolders": {
+                            "changeNotifications": true,
+                            "supported": true,
+                        }
+                    },
+                    "hoverProvider": true,
+                    "signatureHelpProvider": {
+                        "triggerCharacters": ["(", ","],
+                    },
+                    "definitionProvider": true,
+                    "referencesProvider": true,
+                    "documentHighlightProvider": true,
+                    "documentSymbolProvider": true,
+                    "workspaceSymbolProvider": true,
+                    "semanticTokensProvider": {
+                        "legend": {
+                            "tokenTypes": [
+                                "keyword", "operator", "string", "number", "regexp", "comment",
+                                "namespace", "type", "function", "parameter", "variable",
+                                "property"
+                            ],
+                            "tokenModifiers": [
+                                "declaration", "readonly", "documentation", "defaultLibrary"
+                            ]
+                        },
+                        "range": true,
+                        "full": {"delta": true}
+                    },
+                    "documentFormattingProvider": true,
+                    "documentRangeFormattingProvider": true,
+                    "documentOnTypeFormattingProvider": {
+                        "firstTriggerCharacter": ";",
+                        "moreTriggerCharacter": ["d"]
+                    },
+                    "renameProvider": {"prepareProvider": true}
+                },
+                "serverInfo": {
+                    "name": "tremor-language-server",
+                    "version": VERSION
+                }
+            }),
+            res.result().unwrap()
+        );
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn warning_class() {
+        tracing_subscriber::fmt::init();
+
+        let (mut service, mut socket) =
+            LspService::new(|client| Backend::new(client, "tremor-deploy", module_path()));
+
+        let join_handle = async_std::task::spawn(async move {
+            while let Some(x) = socket.next().await {
+                if x.method() == "textDocument/publishDiagnostics" {
+                    let params = x.params();
+
+                    if let Some(params) = params {
+                        let message = params.get("diagnostics").unwrap().as_array().unwrap()[0]
+                            .get("message")
+                            .unwrap()
+                            .as_str()
+                            .unwrap()
+                            .to_string();
+
+                        return Some(message);
+                    }
+                }
+            }
+
+            None
+        });
+
+        let initialize_req = Request::build("initialize")
+            .params(json!({"capabilities":{
+            "textDocument": {
+                "synchronization": {
+                    "dynamicRegistration": true,
+                }
+            }}}))
+            .id(1)
+            .finish();
+
+        let _initialize_res = service
+            .call(initialize_req)
+            .await
+            .expect("Expect request to be executed");
+
+        let initialized_req = Request::build("initialized").params(json!({})).finish();
+        let _initialized_res = service
+            .call(initialized_req)
+            .await
+            .expect("Expect request to be executed");
+
+        let req = Request::build("textDocument/didOpen")
+            .params(json!({"textDocument": {
+                "uri": format!("file://{}/{}", env!("CARGO_MANIFEST_DIR"), "tests/warning_class.tremor"),
+                "languageId": "tremor-deploy",
+                "version": 1,
+                "text": include_str!("../tests/warning_class.tremor")
+            }}))
+            .finish();
+        let _res = service
+            .call(req)
+            .await
+            .expect("Expect request to be executed");
+
+        let result = join_handle.timeout(std::time::Duration::from_secs(5)).await;
+
+        assert_eq!(
+            result,
+            Ok(Some(
+                "const's are canonically written in UPPER_CASE".to_string()
+            ))
+        );
+    }
+
+    #[async_std::test]
+    async fn script_warnings() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut service, mut socket) =
+            LspService::new(|client| Backend::new(client, "tremor-script", module_path()));
+
+        let join_handle = async_std::task::spawn(async move {
+            while let Some(x) = socket.next().await {
+                if x.method() == "textDocument/publishDiagnostics" {
+                    return x.params().cloned();
+                }
+            }
+            None
+        });
+
+        initialize(&mut service).await;
+        open(
+            &mut service,
+            &file_uri(&dir, "warnings.tremor"),
+            "const answer = 42;\nfn f() with\n  answer\nend;\n",
+        )
+        .await;
+
+        let params = join_handle
+            .timeout(std::time::Duration::from_secs(5))
+            .await
+            .expect("Expect diagnostics to be published")
+            .expect("Expect diagnostics params");
+        let diagnostics = params["diagnostics"].as_array().unwrap();
+        assert_eq!(1, diagnostics.len());
+        assert_eq!(json!("consistency"), diagnostics[0]["code"]);
+        assert_eq!(json!(2), diagnostics[0]["severity"]);
+        assert_eq!(
+            json!({"line": 0, "character": 0}),
+            diagnostics[0]["range"]["start"]
+        );
+    }
+
+    #[async_std::test]
+    async fn diagnostic_codes() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut service, mut socket) =
+            LspService::new(|client| Backend::new(client, "tremor-script", module_path()));
+
+        let join_handle = async_std::task::spawn(async move {
+            while let Some(x) = socket.next().await {
+                if x.method() == "textDocument/publishDiagnostics" {
+                    return x.params().cloned();
+                }
+            }
+            None
+        });
+
+        initialize(&mut service).await;
+        let uri = &file_uri(&dir, "codes.trickle");
+        open(
+            &mut service,
+            uri,
+            "define window w from tumbling\nwith\n  size = 2\nend;\nselect match event of case _ => 1 case _ => 2 end from in into out;\ndefine window w from tumbling\nwith\n  size = 3\nend;\nselect for event of case (k, v) => v into 0 use + end from in into out;\n",
+        )
+        .await;
+
+        let params = join_handle
+            .timeout(std::time::Duration::from_secs(5))
+            .await
+            .expect("Expect diagnostics to be published")
+            .expect("Expect diagnostics params");
+        let diagnostics = params["diagnostics"].as_array().unwrap();
+        assert_eq!(3, diagnostics.len());
+
+        let duplicate = &diagnostics[0];
+        assert_eq!(json!("duplicate-definition"), duplicate["code"]);
+        assert_eq!(
+            json!({"href": "https://www.tremor.rs/docs/0.12/language/query"}),
+            duplicate["codeDescription"]
+        );
+        assert_eq!(json!(5), duplicate["range"]["start"]["line"]);
+        assert_eq!(
+            json!([{
+                "location": {
+                    "uri": uri,
+                    "range": {
+                        "start": {"line": 0, "character": 14},
+                        "end": {"line": 0, "character": 15}
+                    }
+                },
+                "message": "`w` is first defined here"
+            }]),
+            duplicate["relatedInformation"]
+        );
+        assert_eq!(None, duplicate.get("tags"));
+
+        let unreachable = &diagnostics[2];
+        assert_eq!(json!("behaviour"), unreachable["code"]);
+        assert_eq!(json!(4), unreachable["range"]["start"]["line"]);
+        assert_eq!(json!([1]), unreachable["tags"]);
+        assert_eq!(None, unreachable.get("relatedInformation"));
+
+        // experimental syntax that "may be deprecated" isn't deprecated yet
+        let experimental = &diagnostics[1];
+        assert_eq!(json!("general"), experimental["code"]);
+        assert_eq!(json!(9), experimental["range"]["start"]["line"]);
+        assert_eq!(None, experimental.get("tags"));
+    }
+
+    // labels of the completions at a position of the document
+    async fn completion_labels(
+        service: &mut LspService<Backend>,
+        uri: &str,
+        line: u32,
+        character: u32,
+    ) -> Vec<String> {
+        let completions = request(
+            service,
+            "textDocument/completion",
+            json!({
+                "textDocument": {"uri": uri},
+                "position": {"line": line, "character": character}
+            }),
+        )
+        .await;
+        completions
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["label"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[async_std::test]
+    async fn module_function_completions() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut service, mut socket) =
+            LspService::new(|client| Backend::new(client, "tremor-script", module_path()));
+        async_std::task::spawn(async move { while socket.next().await.is_some() {} });
+        initialize(&mut service).await;
+
+        let uri = &file_uri(&dir, "functions.tremor");
+        open(&mut service, uri, "fn f() with\n  string::\nend;\n").await;
+        let labels = completion_labels(&mut service, uri, 1, 10).await;
+        assert!(labels.contains(&"format".to_string()));
+        assert!(labels.contains(&"len".to_string()));
+    }
+
+    #[async_std::test]
+    async fn scope_completions() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut service, mut socket) =
+            LspService::new(|client| Backend::new(client, "tremor-script", module_path()));
+        async_std::task::spawn(async move { while socket.next().await.is_some() {} });
+        initialize(&mut service).await;
+
+        let uri = &file_uri(&dir, "scope.tremor");
+        open(
+            &mut service,
+            uri,
+            "const LIMIT = 10;\nfn add(a, b) with\n  let total = a + b;\n  total\nend;\n\n",
+        )
+        .await;
+        // functions see their arguments and locals, and what is defined at the
+        // top level, which come before the keywords
+        assert_eq!(
+            vec!["LIMIT", "add", "a", "b", "total"],
+            completion_labels(&mut service, uri, 3, 2).await[..5]
+        );
+        let completions = request(
+            &mut service,
+            "textDocument/completion",
+            json!({
+                "textDocument": {"uri": uri},
+                "position": {"line": 3, "character": 2}
+            }),
+        )
+        .await;
+        let add = &completions[1];
+        assert_eq!(json!("fn add(a, b)"), add["detail"]);
+        assert_eq!(json!("add(${1:a}, ${2:b})"), add["insertText"]);
+        assert_eq!(json!(2), add["insertTextFormat"]);
+
+        let uri = &file_uri(&dir, "scope.trickle");
+        open(
+            &mut service,
+            uri,
+            "define window w from tumbling\nwith\n  interval = 1\nend;\nselect event from in[w] group by event.key into out;\ndefine script s\nscript\n  let x = 1;\n  x\nend;\n",
+        )
+        .await;
+        assert_eq!(
+            vec!["event", "state", "$", "args", "x"],
+            completion_labels(&mut service, uri, 8, 2).await[..5]
+        );
+        assert_eq!(
+            vec!["event", "$", "args", "window", "group"],
+            completion_labels(&mut service, uri, 4, 7).await[..5]
+        );
+        // fields aren't variables
+        assert!(completion_labels(&mut service, uri, 4, 39).await.is_empty());
+    }
+
+    #[async_std::test]
+    async fn member_completions() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut service, mut socket) =
+            LspService::new(|client| Backend::new(client, "tremor-script", module_path()));
+        async_std::task::spawn(async move { while socket.next().await.is_some() {} });
+
+        let workspace = dir.path().to_path_buf();
+        std::fs::create_dir_all(workspace.join("shared")).unwrap();
+        std::fs::write(workspace.join("shared/tools.tremor"), "const TOOL = 1;\n").unwrap();
+        initialize_with(
+            &mut service,
+            json!({
+                "capabilities": {},
+                "rootUri": Url::from_directory_path(&workspace).unwrap()
+            }),
+        )
+        .await;
+
+        let uri = &file_uri(&dir, "members.trickle");
+        open(
+            &mut service,
+            uri,
+            "use answers::helpers;\nuse shared::tools;\nselect helpers:: from in into out;\nselect tools::T from in into out;\nselect answers::helpers:: from in into out;\n",
+        )
+        .await;
+        let completions = request(
+            &mut service,
+            "textDocument/completion",
+            json!({
+                "textDocument": {"uri": uri},
+                "position": {"line": 2, "character": 16}
+            }),
+        )
+        .await;
+        assert_eq!(
+            json!([
+                {
+                    "label": "inc",
+                    "kind": 3,
+                    "detail": "fn inc(x)",
+                    "documentation": {"kind": "markdown", "value": "Adds one\nto a number"},
+                    "insertText": "inc(${1:x})",
+                    "insertTextFormat": 2
+                },
+                {
+                    "label": "ANSWER",
+                    "kind": 21,
+                    "detail": "const",
+                    "documentation": {"kind": "markdown", "value": "The answer"}
+                },
+                {
+                    "label": "undocumented",
+                    "kind": 3,
+                    "detail": "fn undocumented()",
+                    "insertText": "undocumented()",
+                    "insertTextFormat": 2
+                }
+            ]),
+            completions
+        );
+        // modules of the workspace, completed while typing a member
+        assert_eq!(
+            vec!["TOOL"],
+            completion_labels(&mut service, uri, 3, 15).await
+        );
+        // modules are only known by the name they are `use`d as
+        assert!(completion_labels(&mut service, uri, 4, 24).await.is_empty());
+    }
+
+    #[async_std::test]
+    async fn keyword_completions() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut service, mut socket) =
+            LspService::new(|client| Backend::new(client, "tremor-script", module_path()));
+        async_std::task::spawn(async move { while socket.next().await.is_some() {} });
+        initialize(&mut service).await;
+
+        // modules hold definitions, but no expressions
+        let uri = &file_uri(&dir, "keywords.tremor");
+        open(
+            &mut service,
+            uri,
+            "const LIMIT = 1;\n\nfn f() with\n  \nend;\n",
+        )
+        .await;
+        assert_eq!(
+            vec![
+                "const",
+                "fn",
+                "use",
+                "define window",
+                "define script",
+                "define operator",
+                "define pipeline",
+                "define connector",
+                "define flow",
+                "define",
+                "intrinsic"
+            ],
+            completion_labels(&mut service, uri, 1, 0).await
+        );
+        let labels = completion_labels(&mut service, uri, 3, 2).await;
+        for label in [
+            "LIMIT", "match", "patch", "merge", "for", "let", "emit", "drop",
+        ] {
+            assert!(labels.contains(&label.to_string()), "{label} in {labels:?}");
+        }
+        assert!(!labels.contains(&"select".to_string()));
+        assert!(!labels.contains(&"event".to_string()));
+        // `}` within placeholders is escaped, not to end them
+        let completions = request(
+            &mut service,
+            "textDocument/completion",
+            json!({"textDocument": {"uri": uri}, "position": {"line": 3, "character": 2}}),
+        )
+        .await;
+        let merge = completions
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|item| item["label"] == "merge")
+            .unwrap();
+        assert_eq!(
+            json!("merge ${1:event} of ${2:{\\}} end"),
+            merge["insertText"]
+        );
+
+        let uri = &file_uri(&dir, "keywords.trickle");
+        open(&mut service, uri, "select event from in \n").await;
+        assert_eq!(
+            vec!["group", "into", "where"],
+            completion_labels(&mut service, uri, 0, 21).await
+        );
+        assert_eq!(
+            vec![
+                "use",
+                "select",
+                "define window",
+                "define script",
+                "define operator",
+                "define pipeline",
+                "create stream",
+                "create operator",
+                "create script",
+                "create pipeline",
+                "create",
+                "define"
+            ],
+            completion_labels(&mut service, uri, 0, 0).await
+        );
+
+        let uri = &file_uri(&dir, "keywords.troy");
+        open(&mut service, uri, "\ndefine flow f\nflow\n  \nend;\n").await;
+        assert_eq!(
+            vec!["use", "define flow", "deploy flow", "define", "deploy"],
+            completion_labels(&mut service, uri, 0, 0).await
+        );
+        assert_eq!(
+            vec![
+                "use",
+                "define pipeline",
+                "define connector",
+                "create pipeline",
+                "create connector",
+                "connect",
+                "create",
+                "define"
+            ],
+            completion_labels(&mut service, uri, 3, 2).await
+        );
+    }
+
+    // titles and edits of the quick fixes for `line` of the document
+    async fn quick_fix(
+        service: &mut LspService<Backend>,
+        uri: &str,
+        text: &str,
+        line: u32,
+    ) -> Vec<(Value, Value)> {
+        open(service, uri, text).await;
+        let actions = request(
+            service,
+            "textDocument/codeAction",
+            json!({
+                "textDocument": {"uri": uri},
+                "range": {
+                    "start": {"line": line, "character": 0},
+                    "end": {"line": line + 1, "character": 0}
+                },
+                "context": {"diagnostics": []}
+            }),
+        )
+        .await;
+        actions
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|action| {
+                assert_eq!(json!("quickfix"), action["kind"]);
+                (
+                    action["title"].clone(),
+                    action["edit"]["changes"][uri].clone(),
+                )
+            })
+            .collect()
+    }
+
+    #[async_std::test]
+    async fn quick_fixes() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut service, mut socket) =
+            LspService::new(|client| Backend::new(client, "tremor-script", module_path()));
+        async_std::task::spawn(async move { while socket.next().await.is_some() {} });
+        initialize(&mut service).await;
+
+        let edit = |line, character, text| {
+            json!([{
+                "range": {
+                    "start": {"line": line, "character": character},
+                    "end": {"line": line, "character": character}
+                },
+                "newText": text
+            }])
+        };
+
+        // a misspelled registry function
+        assert_eq!(
+            vec![(
+                json!("Change to `string::len`"),
+                json!([{
+                    "range": {
+                        "start": {"line": 0, "character": 15},
+                        "end": {"line": 0, "character": 19}
+                    },
+                    "newText": "len"
+                }])
+            )],
+            quick_fix(
+                &mut service,
+                &file_uri(&dir, "quick_fix_function.trickle"),
+                "select string::lenn(event) from in into out;\n",
+                0
+            )
+            .await
+        );
+
+        // a module on the `TREMOR_PATH`, used within the embedded script
+        assert_eq!(
+            vec![(
+                json!("Add `use answers::loud;`"),
+                edit(2, 0, "  use answers::loud;\n")
+            )],
+            quick_fix(
+                &mut service,
+                &file_uri(&dir, "quick_fix_use.trickle"),
+                "define script s\nscript\n  loud::shout(event)\nend;\n",
+                2
+            )
+            .await
+        );
+
+        // missing terminators
+        assert_eq!(
+            vec![(json!("Add missing `;`"), edit(0, 29, ";"))],
+            quick_fix(
+                &mut service,
+                &file_uri(&dir, "quick_fix_semi.trickle"),
+                "select event from in into out\nselect event from in into out;\n",
+                1
+            )
+            .await
+        );
+        assert_eq!(
+            vec![
+                (json!("Add missing `end;`"), edit(1, 3, "\nend;")),
+                (json!("Add missing `;`"), edit(1, 3, ";"))
+            ],
+            quick_fix(
+                &mut service,
+                &file_uri(&dir, "quick_fix_end.tremor"),
+                "fn f() with\n  1\n\nfn g() with 2 end;\n",
+                3
+            )
+            .await
+        );
+
+        // consts are renamed to UPPER_CASE, along with their references
+        let fixes = quick_fix(
+            &mut service,
+            &file_uri(&dir, "quick_fix_const.troy"),
+            include_str!("../tests/warning_class.tremor"),
+            6,
+        )
+        .await;
+        assert_eq!(1, fixes.len());
+        assert_eq!(json!("Rename to `A`"), fixes[0].0);
+        assert_eq!(
+            json!([{
+                "range": {
+                    "start": {"line": 6, "character": 18},
+                    "end": {"line": 6, "character": 19}
+                },
+                "newText": "A"
+            }]),
+            fixes[0].1
+        );
+    }
+
+    #[async_std::test]
+    async fn pull_diagnostics() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut service, mut socket) = Backend::service("tremor-script", module_path());
+        async_std::task::spawn(async move { while socket.next().await.is_some() {} });
+
+        let folder = dir.path().to_path_buf();
+        std::fs::create_dir_all(&folder).unwrap();
+        let broken = folder.join("broken.trickle");
+        let fine = folder.join("fine.tremor");
+        std::fs::write(
+            &broken,
+            "select event from in into out\nselect event from in into out;\n",
+        )
+        .unwrap();
+        std::fs::write(&fine, "const ANSWER = 42;\n").unwrap();
+        let (broken, fine) = (
+            Url::from_file_path(broken).unwrap().to_string(),
+            Url::from_file_path(fine).unwrap().to_string(),
+        );
+        initialize_with(
+            &mut service,
+            json!({
+                "capabilities": {},
+                "workspaceFolders": [{
+                    "uri": Url::from_directory_path(folder).unwrap(),
+                    "name": "diagnostics"
+                }]
+            }),
+        )
+        .await;
+        let uri = &file_uri(&dir, "pull.tremor");
+        open(&mut service, uri, "const answer = 42;\n").await;
+
+        let report = request(
+            &mut service,
+            "textDocument/diagnostic",
+            json!({"textDocument": {"uri": uri}}),
+        )
+        .await;
+        assert_eq!(json!("full"), report["kind"]);
+        assert_eq!(json!("consistency"), report["items"][0]["code"]);
+        let result_id = report["resultId"].clone();
+        assert_eq!(
+            json!({"kind": "unchanged", "resultId": result_id}),
+            request(
+                &mut service,
+                "textDocument/diagnostic",
+                json!({"textDocument": {"uri": uri}, "previousResultId": result_id}),
+            )
+            .await
+        );
+
+        let reports = |report: &Value| -> Vec<(String, Value, Value, usize)> {
+            let mut reports: Vec<_> = report["items"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|r| {
+                    (
+                        r["uri"].as_str().unwrap().to_string(),
+                        r["version"].clone(),
+                        r["kind"].clone(),
+                        r["items"].as_array().map_or(0, Vec::len),
+                    )
+                })
+                .collect();
+            reports.sort_by(|a, b| a.0.cmp(&b.0));
+            reports
+        };
+        let report = request(
+            &mut service,
+            "workspace/diagnostic",
+            json!({"previousResultIds": []}),
+        )
+        .await;
+        let mut expected = vec![
+            (uri.to_string(), json!(1), json!("full"), 1),
+            (broken.clone(), Value::Null, json!("full"), 1),
+            (fine.clone(), Value::Null, json!("full"), 0),
+        ];
+        expected.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(expected, reports(&report));
+        let previous: Vec<Value> = report["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|r| r["uri"] == json!(fine))
+            .map(|r| json!({"uri": r["uri"], "value": r["resultId"]}))
+            .collect();
+        let report = request(
+            &mut service,
+            "workspace/diagnostic",
+            json!({ "previousResultIds": previous }),
+        )
+        .await;
+        assert!(reports(&report).contains(&(fine, Value::Null, json!("unchanged"), 0)));
+    }
+
+    #[async_std::test]
+    async fn debounced_diagnostics() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut service, mut socket) =
+            LspService::new(|client| Backend::new(client, "tremor-script", module_path()));
+
+        // versions diagnostics are published for, until the last one
+        let join_handle = async_std::task::spawn(async move {
+            let mut versions = Vec::new();
+            while let Some(x) = socket.next().await {
+                if x.method() == "textDocument/publishDiagnostics" {
+                    let version = x.params().unwrap()["version"].as_i64().unwrap();
+                    versions.push(version);
+                    if version == 4 {
+                        break;
+                    }
+                }
+            }
+            versions
+        });
+
+        initialize_with(
+            &mut service,
+            json!({"capabilities": {}, "initializationOptions": {"diagnosticsDebounce": 50}}),
+        )
+        .await;
+        let uri = &file_uri(&dir, "debounced.tremor");
+        open(&mut service, uri, "const A = 1;\n").await;
+        for version in 2..=4 {
+            let change = Request::build("textDocument/didChange")
+                .params(json!({
+                    "textDocument": {"uri": uri, "version": version},
+                    "contentChanges": [{"text": format!("const A = {version};\n")}]
+                }))
+                .finish();
+            service.call(change).await.unwrap();
+        }
+
+        let versions = join_handle
+            .timeout(std::time::Duration::from_secs(5))
+            .await
+            .expect("Expect diagnostics to be published");
+        // superseded versions are never analysed, which the opened one may
+        // already be by the time it is
+        assert!(
+            versions == vec![1, 4] || versions == vec![4],
+            "{versions:?}"
+        );
+    }
+
+    #[async_std::test]
+    async fn dependent_diagnostics() {
+        // modules of its own, as one of them is changed
+        let dir = tempfile::tempdir().unwrap();
+        let modules = dir.path().join("modules");
+        let numbers = modules.join("dependents/numbers.tremor");
+        let wrapped = modules.join("dependents/wrapped.tremor");
+        std::fs::create_dir_all(modules.join("dependents")).unwrap();
+        std::fs::write(&numbers, "fn one() with 1 end;\n").unwrap();
+        std::fs::write(
+            &wrapped,
+            "use dependents::numbers;\nfn one() with numbers::one() end;\n",
+        )
+        .unwrap();
+        let module_path = ModulePath {
+            mounts: vec![modules.to_string_lossy().to_string()],
+        };
+        let (mut service, mut socket) =
+            LspService::new(|client| Backend::new(client, "tremor-script", module_path));
+
+        // the diagnostics published after the used module changed, by file name
+        let join_handle = async_std::task::spawn(async move {
+            let mut published = std::collections::BTreeMap::new();
+            while let Some(x) = socket.next().await {
+                if x.method() == "textDocument/publishDiagnostics" {
+                    let params = x.params().unwrap();
+                    let diagnostics = params["diagnostics"].as_array().unwrap().len();
+                    if diagnostics > 0 {
+                        let uri = params["uri"].as_str().unwrap();
+                        let name = uri.rsplit('/').next().unwrap().to_string();
+                        published.insert(name, params["version"].clone());
+                        if published.len() == 2 {
+                            break;
+                        }
+                    }
+                }
+            }
+            published
+        });
+
+        initialize_with(
+            &mut service,
+            json!({"capabilities": {}, "initializationOptions": {"diagnoseWorkspaceDependents": true}}),
+        )
+        .await;
+        let changed = Request::build("workspace/didChangeWatchedFiles")
+            .params(json!({"changes": [
+                {"uri": Url::from_file_path(&wrapped).unwrap(), "type": 1}
+            ]}))
+            .finish();
+        service.call(changed).await.unwrap();
+        open(
+            &mut service,
+            &file_uri(&dir, "dependent.tremor"),
+            "use dependents::wrapped;\nwrapped::one()\n",
+        )
+        .await;
+
+        // the module the used one uses loses its function
+        std::fs::write(&numbers, "fn two() with 2 end;\n").unwrap();
+        let saved = Request::build("textDocument/didSave")
+            .params(json!({"textDocument": {"uri": Url::from_file_path(&numbers).unwrap()}}))
+            .finish();
+        service.call(saved).await.unwrap();
+
+        let published = join_handle
+            .timeout(std::time::Duration::from_secs(5))
+            .await
+            .expect("Expect diagnostics to be published for the dependents");
+        assert_eq!(Some(&json!(1)), published.get("dependent.tremor"));
+        assert_eq!(Some(&Value::Null), published.get("wrapped.tremor"));
+    }
+
+    #[async_std::test]
+    async fn language_per_document() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut service, mut socket) =
+            LspService::new(|client| Backend::new(client, "tremor-script", module_path()));
+
+        // the number of diagnostics published, by document
+        let join_handle = async_std::task::spawn(async move {
+            let mut published = std::collections::BTreeMap::new();
+            while let Some(x) = socket.next().await {
+                if x.method() == "textDocument/publishDiagnostics" {
+                    let params = x.params().unwrap();
+                    let uri = params["uri"].as_str().unwrap().to_string();
+                    published.insert(uri, params["diagnostics"].as_array().unwrap().len());
+                    if published.len() == 4 {
+                        break;
+                    }
+                }
+            }
+            published
+        });
+
+        initialize(&mut service).await;
+        // a query, which is no valid script
+        let text = "select event from in into out;\n";
+        for (uri, language_id) in [
+            ("untitled:query", "tremor-query"),
+            ("untitled:script", "tremor-script"),
+            // the language id goes before the extension
+            (&file_uri(&dir, "language_id.tremor"), "tremor-query"),
+            // which is used for unknown ids
+            (&file_uri(&dir, "language_id.trickle"), "plaintext"),
+        ] {
+            let req = Request::build("textDocument/didOpen")
+                .params(json!({"textDocument": {
+                    "uri": uri,
+                    "languageId": language_id,
+                    "version": 1,
+                    "text": text
+                }}))
+                .finish();
+            service.call(req).await.unwrap();
+        }
+
+        let published = join_handle
+            .timeout(std::time::Duration::from_secs(5))
+            .await
+            .expect("Expect diagnostics to be published");
+        assert_eq!(Some(&0), published.get("untitled:query"));
+        assert_ne!(Some(&0), published.get("untitled:script"));
+        assert_eq!(
+            Some(&0),
+            published.get(&file_uri(&dir, "language_id.tremor"))
+        );
+        assert_eq!(
+            Some(&0),
+            published.get(&file_uri(&dir, "language_id.trickle"))
+        );
+    }
+
+    #[async_std::test]
+    async fn did_open_untitled() {
+        let (mut service, mut socket) =
+            LspService::new(|client| Backend::new(client, "tremor-deploy", module_path()));
+
+        let join_handle = async_std::task::spawn(async move {
+            while let Some(x) = socket.next().await {
+                if x.method() == "textDocument/publishDiagnostics" {
+                    return x.params().cloned();
+                }
+            }
+            None
+        });
+
+        initialize(&mut service).await;
+        let req = Request::build("textDocument/didOpen")
+            .params(json!({"textDocument": {
+                "uri": "untitled:Untitled-1",
+                "languageId": "tremor-script",
+                "version": 3,
+                "text": "let a = ;"
+            }}))
+            .finish();
+        service
+            .call(req)
+            .await
+            .expect("Expect request to be executed");
+
+        let params = join_handle
+            .timeout(std::time::Duration::from_secs(5))
+            .await
+            .expect("Expect diagnostics to be published")
+            .expect("Expect diagnostics params");
+        assert_eq!(Some(&json!(3)), params.get("version"));
+        assert_eq!(
+            1,
+            params.get("diagnostics").unwrap().as_array().unwrap().len()
+        );
+    }
+
+    #[async_std::test]
+    async fn all_parse_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut service, mut socket) =
+            LspService::new(|client| Backend::new(client, "tremor-script", module_path()));
+
+        let join_handle = async_std::task::spawn(async move {
+            while let Some(x) = socket.next().await {
+                if x.method() == "textDocument/publishDiagnostics" {
+                    return x.params().cloned();
+                }
+            }
+            None
+        });
+
+        initialize(&mut service).await;
+        open(
+            &mut service,
+            &file_uri(&dir, "errors.trickle"),
+            "define window w from tumbling\nwith\n  size = 2 +\nend;\nselect event from in into out\nselect event.a + from in into out;\n",
+        )
+        .await;
+
+        let params = join_handle
+            .timeout(std::time::Duration::from_secs(5))
+            .await
+            .expect("Expect diagnostics to be published")
+            .expect("Expect diagnostics params");
+        let starts: Vec<Value> = params["diagnostics"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|d| d["range"]["start"].clone())
+            .collect();
+        assert_eq!(
+            vec![
+                // `end` instead of the rest of the expression
+                json!({"line": 3, "character": 0}),
+                // a missing `;` shows at the start of the next statement
+                json!({"line": 5, "character": 0}),
+                // `from` instead of the rest of the expression
+                json!({"line": 5, "character": 17}),
+            ],
+            starts
+        );
+    }
+
+    #[async_std::test]
+    async fn language_from_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut service, mut socket) =
+            LspService::new(|client| Backend::new(client, "tremor-script", module_path()));
+
+        let join_handle = async_std::task::spawn(async move {
+            while let Some(x) = socket.next().await {
+                if x.method() == "textDocument/publishDiagnostics" {
+                    return x.params().cloned();
+                }
+            }
+            None
+        });
+
+        initialize(&mut service).await;
+        // unknown language id, so the deploy language is picked from the extension
+        let req = Request::build("textDocument/didOpen")
+            .params(json!({"textDocument": {
+                "uri": &file_uri(&dir, "flow.troy"),
+                "languageId": "plaintext",
+                "version": 1,
+                "text": "define flow test\nflow\n  define pipeline p\n  pipeline\n    select event from in into out;\n  end;\nend;\ndeploy flow test;\n"
+            }}))
+            .finish();
+        service
+            .call(req)
+            .await
+            .expect("Expect request to be executed");
+
+        let params = join_handle
+            .timeout(std::time::Duration::from_secs(5))
+            .await
+            .expect("Expect diagnostics to be published")
+            .expect("Expect diagnostics params");
+        assert_eq!(Some(&json!([])), params.get("diagnostics"));
+    }
+
+    #[async_std::test]
+    async fn signature_help() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut service, mut socket) =
+            LspService::new(|client| Backend::new(client, "tremor-script", module_path()));
+        async_std::task::spawn(async move { while socket.next().await.is_some() {} });
+        initialize(&mut service).await;
+
+        let uri = &file_uri(&dir, "signatures.tremor");
+        open(
+            &mut service,
+            uri,
+            "fn add(a, b) with\n  a + b\nend;\nfn f(x) with\n  string::format(\"{} {}\", add(x, ), x, array::len([]))\nend;\n",
+        )
+        .await;
+        let signature_help = |character: u32| {
+            json!({
+                "textDocument": {"uri": uri},
+                "position": {"line": 4, "character": character}
+            })
+        };
+
+        let format = json!({
+            "label": "string::format(format, ...)",
+            "documentation": {"kind": "markdown", "value": "Formats a string"},
+            "parameters": [{"label": [15, 21]}, {"label": [23, 26]}]
+        });
+        assert_eq!(
+            json!({"signatures": [format], "activeSignature": 0, "activeParameter": 0}),
+            request(
+                &mut service,
+                "textDocument/signatureHelp",
+                signature_help(17)
+            )
+            .await
+        );
+        // nested calls
+        assert_eq!(
+            json!({
+                "signatures": [{
+                    "label": "add(a, b)",
+                    "parameters": [{"label": [4, 5]}, {"label": [7, 8]}]
+                }],
+                "activeSignature": 0,
+                "activeParameter": 1
+            }),
+            request(
+                &mut service,
+                "textDocument/signatureHelp",
+                signature_help(32)
+            )
+            .await
+        );
+        // the rest of the arguments of variadic functions
+        assert_eq!(
+            json!({"signatures": [format], "activeSignature": 0, "activeParameter": 1}),
+            request(
+                &mut service,
+                "textDocument/signatureHelp",
+                signature_help(38)
+            )
+            .await
+        );
+        // functions of the registry without declarations
+        assert_eq!(
+            json!({
+                "signatures": [{"label": "array::len(arg1)", "parameters": [{"label": [11, 15]}]}],
+                "activeSignature": 0,
+                "activeParameter": 0
+            }),
+            request(
+                &mut service,
+                "textDocument/signatureHelp",
+                signature_help(50)
+            )
+            .await
+        );
+        // outside of calls
+        assert_eq!(
+            Value::Null,
+            request(
+                &mut service,
+                "textDocument/signatureHelp",
+                signature_help(2)
+            )
+            .await
+        );
+
+        // functions of the registry in modules that are `use`d
+        for (uri, text) in [
+            (
+                &file_uri(&dir, "signatures_use.tremor"),
+                "use std::array;\narray::len([])\n",
+            ),
+            (
+                &file_uri(&dir, "signatures_use.trickle"),
+                "use std::array;\narray::len([])\n",
+            ),
+        ] {
+            open(&mut service, uri, text).await;
+            assert_eq!(
+                json!({
+                    "signatures": [{"label": "array::len(arg1)", "parameters": [{"label": [11, 15]}]}],
+                    "activeSignature": 0,
+                    "activeParameter": 0
+                }),
+                request(
+                    &mut service,
+                    "textDocument/signatureHelp",
+                    json!({
+                        "textDocument": {"uri": uri},
+                        "position": {"line": 1, "character": 11}
+                    })
+                )
+                .await
+            );
+        }
+    }
+
+    #[async_std::test]
+    async fn hover() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut service, mut socket) =
+            LspService::new(|client| Backend::new(client, "tremor-script", module_path()));
+        // the documents analysed, whose problems hovers show from then on
+        let (sender, analysed) = async_std::channel::unbounded();
+        async_std::task::spawn(async move {
+            while let Some(x) = socket.next().await {
+                if x.method() == "textDocument/publishDiagnostics" {
+                    let uri = x.params().unwrap()["uri"].as_str().unwrap().to_string();
+                    if sender.send(uri).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        initialize(&mut service).await;
+
+        let uri = &file_uri(&dir, "hover.tremor");
+        open(
+            &mut service,
+            uri,
+            "const LIMIT = 10;\nconst MAX = LIMIT;\nfn f(x) with\n  let y = \"snot\";\n  let y = -1.5;\n  let z = y;\n  patch x of insert \"a\" => z + 1 end\nend;\n",
+        )
+        .await;
+        let hover_at = |uri: &str, line: u32, character: u32| {
+            json!({
+                "textDocument": {"uri": uri},
+                "position": {"line": line, "character": character}
+            })
+        };
+        let hover = |line: u32, character: u32| hover_at(uri, line, character);
+        let value = |hover: Value| hover["contents"]["value"].clone();
+
+        // literal values are inferred through the names assigned
+        assert_eq!(
+            json!("```tremor\nconst MAX = LIMIT;\n```\n\nvalue: `10`"),
+            value(request(&mut service, "textDocument/hover", hover(1, 7)).await)
+        );
+        // the range of the hovered name
+        assert_eq!(
+            json!({"start": {"line": 1, "character": 6}, "end": {"line": 1, "character": 9}}),
+            request(&mut service, "textDocument/hover", hover(1, 7)).await["range"]
+        );
+        // the assignment in effect for rebound locals
+        assert_eq!(
+            json!("```tremor\nlet y = -1.5;\n```\n\nvalue: `-1.5`"),
+            value(request(&mut service, "textDocument/hover", hover(5, 10)).await)
+        );
+        assert_eq!(
+            json!("```tremor\nlet y = \"snot\";\n```\n\nvalue: `\"snot\"`"),
+            value(request(&mut service, "textDocument/hover", hover(3, 6)).await)
+        );
+        assert_eq!(
+            json!("```tremor\nlet z = y;\n```\n\nvalue: `-1.5`"),
+            value(request(&mut service, "textDocument/hover", hover(6, 27)).await)
+        );
+        // arguments have no value to show
+        assert_eq!(
+            Value::Null,
+            request(&mut service, "textDocument/hover", hover(6, 8)).await
+        );
+        let patch = value(request(&mut service, "textDocument/hover", hover(6, 3)).await);
+        assert!(patch
+            .as_str()
+            .unwrap()
+            .starts_with("`patch target of ... end`"));
+
+        // problems at the cursor come first, along with their hint
+        let uri = &file_uri(&dir, "hover_problems.tremor");
+        open(&mut service, uri, "const answer = 42;\n").await;
+        while analysed.recv().await.unwrap() != *uri {}
+        assert_eq!(
+            json!("**warning** `consistency`: const's are canonically written in UPPER_CASE\n\n---\n\n```tremor\nconst answer = 42;\n```\n\nvalue: `42`"),
+            value(request(&mut service, "textDocument/hover", hover_at(uri, 0, 7)).await)
+        );
+        let uri = &file_uri(&dir, "hover_problems.trickle");
+        open(
+            &mut service,
+            uri,
+            "define window w from tumbling with interval = 1 end;\nselect event from in[w] into out;\n",
+        )
+        .await;
+        while analysed.recv().await.unwrap() != *uri {}
+        let problem = value(request(&mut service, "textDocument/hover", hover_at(uri, 1, 9)).await);
+        assert!(problem.as_str().unwrap().starts_with(
+            "**error** `no-event-references-allowed`: References to `event` or `$` are not allowed in this context\n\nNote: Here you operate in the whole window"
+        ));
+
+        let uri = &file_uri(&dir, "hover.trickle");
+        open(
+            &mut service,
+            uri,
+            "define operator batch from generic::batch\nwith\n  count = 3\nend;\ncreate operator b from batch;\n",
+        )
+        .await;
+        assert_eq!(
+            json!(
+                "```tremor\ndefine operator batch from generic::batch\nwith\n  count = 3\nend\n```"
+            ),
+            value(
+                request(
+                    &mut service,
+                    "textDocument/hover",
+                    json!({
+                        "textDocument": {"uri": uri},
+                        "position": {"line": 4, "character": 24}
+                    })
+                )
+                .await
+            )
+        );
+    }
+
+    // applies the edits of a formatting request, which each replace a line
+    fn apply_line_edits(text: &str, edits: &Value) -> String {
+        let mut lines: Vec<String> = text.split('\n').map(ToString::to_string).collect();
+        for edit in edits.as_array().unwrap() {
+            let line = edit["range"]["start"]["line"].as_u64().unwrap();
+            lines[usize::try_from(line).unwrap()] = edit["newText"].as_str().unwrap().to_string();
+        }
+        lines.join("\n")
+    }
+
+    #[async_std::test]
+    async fn formatting() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut service, mut socket) =
+            LspService::new(|client| Backend::new(client, "tremor-script", module_path()));
+        async_std::task::spawn(async move { while socket.next().await.is_some() {} });
+        initialize(&mut service).await;
+        let options = json!({"tabSize": 2, "insertSpaces": true});
+
+        for (uri, text, formatted) in [
+            (
+                &file_uri(&dir, "format.tremor"),
+                "## Describes\nfn describe(e) of\ncase %{ a } =>\nlet x   =\n[1,2 ,3];\nx\n    case _ => match e of\n  case 1 => \"one\" # one\ndefault =>\n   patch e of\ninsert \"a\" => 1\ndefault \"b\" => 2\nend\nend\nend;\n\nconst DOC = \"\"\"\n  kept\n    as is\n  \"\"\";\n",
+                "## Describes\nfn describe(e) of\n  case %{ a } =>\n    let x =\n      [1, 2, 3];\n    x\n  case _ => match e of\n    case 1 => \"one\" # one\n    default =>\n      patch e of\n        insert \"a\" => 1\n        default \"b\" => 2\n      end\n    end\nend;\n\nconst DOC = \"\"\"\n  kept\n    as is\n  \"\"\";\n",
+            ),
+            (
+                &file_uri(&dir, "format.trickle"),
+                "define window w from tumbling\nwith\ninterval = 1\nend;\ndefine script s\nargs\nx = 1\nscript\nemit {\n\"a\": args.x,\n\"b\": [\n1\n]\n}\nend;\ndefine pipeline p\npipeline\ncreate script t from s with x = 2 end;\nselect event from in into out;\nend;\n",
+                "define window w from tumbling\nwith\n  interval = 1\nend;\ndefine script s\nargs\n  x = 1\nscript\n  emit {\n    \"a\": args.x,\n    \"b\": [\n      1\n    ]\n  }\nend;\ndefine pipeline p\npipeline\n  create script t from s with x = 2 end;\n  select event from in into out;\nend;\n",
+            ),
+            (
+                &file_uri(&dir, "format.troy"),
+                "define flow f\nflow\ndefine connector c from metronome\nwith\nconfig = {\"interval\": 1}\nend;\ncreate connector c;\nconnect /connector/c to /pipeline/p;\nend;\n",
+                "define flow f\nflow\n  define connector c from metronome\n  with\n    config = {\"interval\": 1}\n  end;\n  create connector c;\n  connect /connector/c to /pipeline/p;\nend;\n",
+            ),
+        ] {
+            open(&mut service, uri, text).await;
+            let edits = request(
+                &mut service,
+                "textDocument/formatting",
+                json!({"textDocument": {"uri": uri}, "options": options}),
+            )
+            .await;
+            assert_eq!(formatted, apply_line_edits(text, &edits));
+        }
+
+        // only the lines in the range
+        let uri = &file_uri(&dir, "format.trickle");
+        let edits = request(
+            &mut service,
+            "textDocument/rangeFormatting",
+            json!({
+                "textDocument": {"uri": uri},
+                "range": {"start": {"line": 5, "character": 0}, "end": {"line": 7, "character": 0}},
+                "options": options
+            }),
+        )
+        .await;
+        assert_eq!(
+            json!([{
+                "range": {"start": {"line": 6, "character": 0}, "end": {"line": 6, "character": 5}},
+                "newText": "  x = 1"
+            }]),
+            edits
+        );
+
+        // the line of an `end` or `;` just typed
+        let uri = &file_uri(&dir, "format_on_type.tremor");
+        open(
+            &mut service,
+            uri,
+            "fn f() with\n  let end_ = 1;\n  end_\n  end",
+        )
+        .await;
+        let on_type = |line: u32, character: u32, ch: &str| {
+            json!({
+                "textDocument": {"uri": uri},
+                "position": {"line": line, "character": character},
+                "ch": ch,
+                "options": {"tabSize": 4, "insertSpaces": false}
+            })
+        };
+        assert_eq!(
+            json!([{
+                "range": {"start": {"line": 3, "character": 0}, "end": {"line": 3, "character": 5}},
+                "newText": "end"
+            }]),
+            request(
+                &mut service,
+                "textDocument/onTypeFormatting",
+                on_type(3, 5, "d")
+            )
+            .await
+        );
+        assert_eq!(
+            json!([{
+                "range": {"start": {"line": 1, "character": 0}, "end": {"line": 1, "character": 15}},
+                "newText": "\tlet end_ = 1;"
+            }]),
+            request(
+                &mut service,
+                "textDocument/onTypeFormatting",
+                on_type(1, 15, ";")
+            )
+            .await
+        );
+        // not at the end of `end`
+        assert_eq!(
+            Value::Null,
+            request(
+                &mut service,
+                "textDocument/onTypeFormatting",
+                on_type(1, 8, "d")
             )
-            .map(|hover_content| Hover {
-                contents: HoverContents::Markup(hover_content),
-                range: None,
+            .await
+        );
+        // nor within strings and comments
+        let uri = &file_uri(&dir, "format_on_type_literal.tremor");
+        open(
+            &mut service,
+            uri,
+            "fn f() with\n    \"end;\" # end;\nend;\n",
+        )
+        .await;
+        for (character, ch) in [(8, "d"), (9, ";"), (17, "d"), (18, ";")] {
+            let params = json!({
+                "textDocument": {"uri": uri},
+                "position": {"line": 1, "character": character},
+                "ch": ch,
+                "options": {"tabSize": 2, "insertSpaces": true}
             });
+            assert_eq!(
+                Value::Null,
+                request(&mut service, "textDocument/onTypeFormatting", params).await
+            );
+        }
 
-        Ok(result)
+        // the configured indentation wins over the client's
+        let (mut service, mut socket) =
+            LspService::new(|client| Backend::new(client, "tremor-script", module_path()));
+        async_std::task::spawn(async move { while socket.next().await.is_some() {} });
+        initialize_with(
+            &mut service,
+            json!({"capabilities": {}, "initializationOptions": {"indentWidth": 4}}),
+        )
+        .await;
+        let uri = &file_uri(&dir, "format_width.tremor");
+        open(&mut service, uri, "fn f() with\n1\nend;\n").await;
+        let edits = request(
+            &mut service,
+            "textDocument/formatting",
+            json!({"textDocument": {"uri": uri}, "options": options}),
+        )
+        .await;
+        assert_eq!(
+            "fn f() with\n    1\nend;\n",
+            apply_line_edits("fn f() with\n1\nend;\n", &edits)
+        );
     }
-}
 
-// TODO remove. just for testing right now
-pub(crate) fn file_dbg(name: &str, content: &str) {
-    use std::env::temp_dir;
-    use std::fs::File;
-    use std::io::Write;
-    use std::path::PathBuf;
+    #[async_std::test]
+    async fn goto_definition() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut service, mut socket) =
+            LspService::new(|client| Backend::new(client, "tremor-script", module_path()));
+        async_std::task::spawn(async move { while socket.next().await.is_some() {} });
 
-    let mut path = PathBuf::new();
-    path.push(temp_dir());
-    path.push(format!("tremor_{name}"));
+        let folder = dir.path().to_path_buf();
+        std::fs::create_dir_all(folder.join("shared")).unwrap();
+        std::fs::write(folder.join("shared/tools.tremor"), "const TOOL = 1;\n").unwrap();
+        initialize_with(
+            &mut service,
+            json!({
+                "capabilities": {},
+                "workspaceFolders": [{
+                    "uri": Url::from_directory_path(&folder).unwrap(),
+                    "name": "definition"
+                }]
+            }),
+        )
+        .await;
 
-    let mut output = File::create(path).unwrap();
-    write!(output, "{content}").unwrap();
-}
+        let uri = &file_uri(&dir, "definition.tremor");
+        open(
+            &mut service,
+            uri,
+            "use answers::deep;\nlet a = deep::ANSWER;\nlet b = a + 1;\nuse shared::tools;\nlet c = tools::TOOL;\n",
+        )
+        .await;
+        let definition = |line, character| {
+            json!({
+                "textDocument": {"uri": uri},
+                "position": {"line": line, "character": character}
+            })
+        };
 
-#[cfg(test)]
-mod tests {
-    use async_std::prelude::{FutureExt, StreamExt};
-    use serde_json::json;
-    use tower::Service;
-    use tower_lsp::jsonrpc::{Id, Request};
-    use tower_lsp::LspService;
+        // a local
+        assert_eq!(
+            json!({"uri": uri, "range": {
+                "start": {"line": 1, "character": 4},
+                "end": {"line": 1, "character": 5}
+            }}),
+            request(&mut service, "textDocument/definition", definition(2, 8)).await
+        );
+        // the alias of a module
+        assert_eq!(
+            json!({"uri": uri, "range": {
+                "start": {"line": 0, "character": 13},
+                "end": {"line": 0, "character": 17}
+            }}),
+            request(&mut service, "textDocument/definition", definition(1, 9)).await
+        );
+        // a member of a module
+        let module_uri = Url::from_file_path(modules().join("answers/deep.tremor")).unwrap();
+        assert_eq!(
+            json!({"uri": module_uri, "range": {
+                "start": {"line": 0, "character": 6},
+                "end": {"line": 0, "character": 12}
+            }}),
+            request(&mut service, "textDocument/definition", definition(1, 16)).await
+        );
+        // a member of a module in a workspace folder, and the module itself
+        let tools_uri = Url::from_file_path(folder.join("shared/tools.tremor")).unwrap();
+        assert_eq!(
+            json!({"uri": tools_uri, "range": {
+                "start": {"line": 0, "character": 6},
+                "end": {"line": 0, "character": 10}
+            }}),
+            request(&mut service, "textDocument/definition", definition(4, 16)).await
+        );
+        assert_eq!(
+            json!({"uri": tools_uri, "range": {
+                "start": {"line": 0, "character": 0},
+                "end": {"line": 0, "character": 0}
+            }}),
+            request(&mut service, "textDocument/definition", definition(3, 14)).await
+        );
+        // a keyword
+        assert_eq!(
+            Value::Null,
+            request(&mut service, "textDocument/definition", definition(2, 1)).await
+        );
+    }
 
-    use super::*;
+    #[async_std::test]
+    async fn references() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut service, socket) =
+            LspService::new(|client| Backend::new(client, "tremor-script", module_path()));
+        let indexed = indexing(socket);
 
-    const VERSION: &str = env!("CARGO_PKG_VERSION");
+        let folder = dir.path().to_path_buf();
+        std::fs::create_dir_all(folder.join("refs")).unwrap();
+        let lib = folder.join("refs/lib.tremor");
+        let main = folder.join("main.tremor");
+        let lib_text = "const ANSWER = 42;\nfn answer() with\n  ANSWER\nend;\n";
+        std::fs::write(&lib, lib_text).unwrap();
+        std::fs::write(
+            &main,
+            "use refs::lib;\nlet a = lib::ANSWER;\nlet b = refs::lib::ANSWER + a;\n",
+        )
+        .unwrap();
+        let folder = Url::from_directory_path(folder).unwrap();
+        initialize_with(
+            &mut service,
+            json!({
+                "capabilities": {},
+                "workspaceFolders": [{"uri": folder, "name": "references"}]
+            }),
+        )
+        .await;
+        indexed.recv().await.unwrap();
 
-    #[async_std::test]
-    async fn backend() -> Result<()> {
-        let lang = language::lookup("tremor-deploy").unwrap();
-        let (mut service, _socket) = LspService::new(|client| Backend::new(client, lang));
-        let req = Request::build("initialize")
-            .params(json!({"capabilities":{}}))
-            .id(1)
-            .finish();
-        let res = service
-            .call(req)
+        let lib = Url::from_file_path(lib).unwrap();
+        let main = Url::from_file_path(main).unwrap();
+        open(&mut service, lib.as_str(), lib_text).await;
+        let location = |uri: &Url, line, start, end| {
+            json!({"uri": uri, "range": {
+                "start": {"line": line, "character": start},
+                "end": {"line": line, "character": end}
+            }})
+        };
+        assert_eq!(
+            json!([
+                location(&lib, 0, 6, 12),
+                location(&lib, 2, 2, 8),
+                location(&main, 1, 13, 19),
+                location(&main, 2, 19, 25),
+            ]),
+            request(
+                &mut service,
+                "textDocument/references",
+                json!({
+                    "textDocument": {"uri": lib},
+                    "position": {"line": 2, "character": 4},
+                    "context": {"includeDeclaration": true}
+                })
+            )
             .await
-            .expect("Expect request to be executed")
-            .expect("Expect response");
-        assert_eq!(res.id(), &Id::Number(1));
-        assert!(res.is_ok());
+        );
+    }
+
+    #[async_std::test]
+    async fn rename_across_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut service, socket) =
+            LspService::new(|client| Backend::new(client, "tremor-script", module_path()));
+        let indexed = indexing(socket);
+
+        let folder = dir.path().to_path_buf();
+        std::fs::create_dir_all(folder.join("refs")).unwrap();
+        let lib = folder.join("refs/lib.tremor");
+        let main = folder.join("main.tremor");
+        let lib_text = "const ANSWER = 42;\n";
+        std::fs::write(&lib, lib_text).unwrap();
+        std::fs::write(
+            &main,
+            "use refs::lib;\nlet a = lib::ANSWER;\nlet b = lib::QUESTION;\n",
+        )
+        .unwrap();
+        let folder = Url::from_directory_path(folder).unwrap();
+        initialize_with(
+            &mut service,
+            json!({
+                "capabilities": {},
+                "workspaceFolders": [{"uri": folder, "name": "rename"}]
+            }),
+        )
+        .await;
+        indexed.recv().await.unwrap();
+
+        let lib = Url::from_file_path(lib).unwrap();
+        let main = Url::from_file_path(main).unwrap();
+        open(&mut service, lib.as_str(), lib_text).await;
+        let rename = |name| {
+            json!({
+                "textDocument": {"uri": lib},
+                "position": {"line": 0, "character": 8},
+                "newName": name
+            })
+        };
+        let edit = |line, start, end| {
+            json!({
+                "range": {
+                    "start": {"line": line, "character": start},
+                    "end": {"line": line, "character": end}
+                },
+                "newText": "ANSWERED"
+            })
+        };
         assert_eq!(
-            &json!({
-                "capabilities": {
-                    "completionProvider": {
-                        "triggerCharacters": [":"],
-                    },
-                    "textDocumentSync": 1,
-                    "workspace": {
-                        "workspaceFolders": {
-                            "changeNotifications": true,
-                            "supported": true,
-                        }
-                    },
-                    "hoverProvider": true
+            json!({"changes": {
+                lib.as_str(): [edit(0, 6, 12)],
+                main.as_str(): [edit(1, 13, 19)],
+            }}),
+            request(&mut service, "textDocument/rename", rename("ANSWERED")).await
+        );
+        // `main` already refers to a `lib::QUESTION`, which doesn't exist yet
+        assert_eq!(
+            format!("`QUESTION` would clash with the reference to it at {main}:3:14"),
+            request_error(&mut service, "textDocument/rename", rename("QUESTION")).await
+        );
+    }
+
+    #[async_std::test]
+    async fn rename() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut service, mut socket) =
+            LspService::new(|client| Backend::new(client, "tremor-script", module_path()));
+        async_std::task::spawn(async move { while socket.next().await.is_some() {} });
+        initialize(&mut service).await;
+
+        let uri = &file_uri(&dir, "rename.tremor");
+        open(
+            &mut service,
+            uri,
+            "const ANSWER = 1;\nlet a = ANSWER;\nlet b = a + 1;\nemit a + b\n",
+        )
+        .await;
+        let position = |line, character| {
+            json!({
+                "textDocument": {"uri": uri},
+                "position": {"line": line, "character": character}
+            })
+        };
+        let rename = |line, character, name| {
+            json!({
+                "textDocument": {"uri": uri},
+                "position": {"line": line, "character": character},
+                "newName": name
+            })
+        };
+        let edit = |line, start, end, text| {
+            json!({
+                "range": {
+                    "start": {"line": line, "character": start},
+                    "end": {"line": line, "character": end}
                 },
-                "serverInfo": {
-                    "name": "tremor-language-server",
-                    "version": VERSION
-                }
+                "newText": text
+            })
+        };
+
+        assert_eq!(
+            json!({
+                "range": {
+                    "start": {"line": 2, "character": 8},
+                    "end": {"line": 2, "character": 9}
+                },
+                "placeholder": "a"
             }),
-            res.result().unwrap()
+            request(&mut service, "textDocument/prepareRename", position(2, 8)).await
         );
-        Ok(())
+        assert_eq!(
+            json!({"changes": {uri: [
+                edit(1, 4, 5, "c"),
+                edit(2, 8, 9, "c"),
+                edit(3, 5, 6, "c"),
+            ]}}),
+            request(&mut service, "textDocument/rename", rename(2, 8, "c")).await
+        );
+        assert_eq!(
+            "`end` is a reserved keyword",
+            request_error(&mut service, "textDocument/rename", rename(2, 8, "end")).await
+        );
+        assert_eq!(
+            "`b` would clash with the local `b`",
+            request_error(&mut service, "textDocument/rename", rename(2, 8, "b")).await
+        );
+        assert_eq!(
+            "const's are canonically written in UPPER_CASE, `answer` is not",
+            request_error(&mut service, "textDocument/rename", rename(1, 10, "answer")).await
+        );
+        assert_eq!(
+            json!({"changes": {uri: [
+                edit(0, 6, 12, "QUESTION"),
+                edit(1, 8, 14, "QUESTION"),
+            ]}}),
+            request(
+                &mut service,
+                "textDocument/rename",
+                rename(1, 10, "QUESTION")
+            )
+            .await
+        );
+    }
+
+    // names and kinds of the symbols in an outline
+    fn outline(symbols: &Value) -> Value {
+        symbols
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|symbol| {
+                let name = &symbol["name"];
+                let kind = &symbol["kind"];
+                if symbol["children"].is_array() {
+                    json!([name, kind, outline(&symbol["children"])])
+                } else {
+                    json!([name, kind])
+                }
+            })
+            .collect()
     }
 
     #[async_std::test]
-    async fn warning_class() {
-        tracing_subscriber::fmt::init();
+    async fn document_symbols() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut service, mut socket) =
+            LspService::new(|client| Backend::new(client, "tremor-script", module_path()));
+        async_std::task::spawn(async move { while socket.next().await.is_some() {} });
+        initialize(&mut service).await;
 
-        let lang = language::lookup("tremor-deploy").unwrap();
-        let (mut service, mut socket) = LspService::new(|client| Backend::new(client, lang));
+        let uri = &file_uri(&dir, "symbols.troy");
+        open(
+            &mut service,
+            uri,
+            "define flow main\nflow\n  define connector out from stdio;\n  define pipeline pass\n  pipeline\n    define script s\n    script\n      emit event\n    end;\n    create script s;\n    select event from in into s;\n    select event from s into out;\n  end;\n  create connector out;\n  create pipeline main from pass;\n  connect /pipeline/main to /connector/out;\nend;\ndeploy flow main;\n",
+        )
+        .await;
+        let symbols = request(
+            &mut service,
+            "textDocument/documentSymbol",
+            json!({"textDocument": {"uri": uri}}),
+        )
+        .await;
+        assert_eq!(
+            json!([
+                [
+                    "main",
+                    3,
+                    [
+                        ["out", 11],
+                        [
+                            "pass",
+                            5,
+                            [["s", 6], ["s", 19], ["select", 24], ["select", 24],]
+                        ],
+                        ["out", 19],
+                        ["main", 19],
+                    ]
+                ],
+                ["main", 4],
+            ]),
+            outline(&symbols)
+        );
+        assert_eq!(json!("stdio"), symbols[0]["children"][0]["detail"]);
+        assert_eq!(
+            json!("from in into s"),
+            symbols[0]["children"][1]["children"][2]["detail"]
+        );
 
-        let join_handle = async_std::task::spawn(async move {
-            while let Some(x) = socket.next().await {
-                if x.method() == "textDocument/publishDiagnostics" {
-                    let params = x.params();
+        let uri = &file_uri(&dir, "symbols.tremor");
+        open(
+            &mut service,
+            uri,
+            "const A = 1;\nfn f(x) with\n  let y = x;\n  y\nend;\nlet z = f(A);\n",
+        )
+        .await;
+        let symbols = request(
+            &mut service,
+            "textDocument/documentSymbol",
+            json!({"textDocument": {"uri": uri}}),
+        )
+        .await;
+        assert_eq!(json!([["A", 14], ["f", 12], ["z", 13]]), outline(&symbols));
+    }
 
-                    if let Some(params) = params {
-                        let message = params.get("diagnostics").unwrap().as_array().unwrap()[0]
-                            .get("message")
-                            .unwrap()
-                            .as_str()
-                            .unwrap()
-                            .to_string();
+    #[async_std::test]
+    async fn symbols_of_invalid_document() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut service, mut socket) =
+            LspService::new(|client| Backend::new(client, "tremor-script", module_path()));
+        async_std::task::spawn(async move { while socket.next().await.is_some() {} });
+        initialize(&mut service).await;
 
-                        return Some(message);
-                    }
-                }
-            }
+        // the window `w` isn't defined, so the deployment has no AST
+        let uri = &file_uri(&dir, "invalid_symbols.troy");
+        open(
+            &mut service,
+            uri,
+            "define flow main\nflow\n  define connector out from stdio;\n  define pipeline pass\n  pipeline\n    define script s\n    script\n      emit event\n    end;\n    create script s;\n    select event from in[w] into s;\n    select event from s into out;\n  end;\n  create connector out;\n  create pipeline main from pass;\n  connect /pipeline/main to /connector/out;\nend;\ndeploy flow main;\n",
+        )
+        .await;
+        let symbols = request(
+            &mut service,
+            "textDocument/documentSymbol",
+            json!({"textDocument": {"uri": uri}}),
+        )
+        .await;
+        assert_eq!(
+            json!([
+                [
+                    "main",
+                    3,
+                    [
+                        ["out", 11],
+                        [
+                            "pass",
+                            5,
+                            [["s", 6], ["s", 19], ["select", 24], ["select", 24],]
+                        ],
+                        ["out", 19],
+                        ["main", 19],
+                    ]
+                ],
+                ["main", 4],
+            ]),
+            outline(&symbols)
+        );
 
-            None
-        });
+        let position = |line, character| {
+            json!({
+                "textDocument": {"uri": uri},
+                "position": {"line": line, "character": character}
+            })
+        };
+        assert_eq!(
+            json!({"uri": uri, "range": {
+                "start": {"line": 3, "character": 18},
+                "end": {"line": 3, "character": 22}
+            }}),
+            request(&mut service, "textDocument/definition", position(14, 28)).await
+        );
+        assert_eq!(
+            json!([
+                {"uri": uri, "range": {
+                    "start": {"line": 5, "character": 18},
+                    "end": {"line": 5, "character": 19}
+                }},
+                {"uri": uri, "range": {
+                    "start": {"line": 9, "character": 18},
+                    "end": {"line": 9, "character": 19}
+                }},
+            ]),
+            request(
+                &mut service,
+                "textDocument/references",
+                json!({
+                    "textDocument": {"uri": uri},
+                    "position": {"line": 5, "character": 18},
+                    "context": {"includeDeclaration": true}
+                })
+            )
+            .await
+        );
+    }
 
-        let initialize_req = Request::build("initialize")
-            .params(json!({"capabilities":{
-            "textDocument": {
-                "synchronization": {
-                    "dynamicRegistration": true,
+    #[async_std::test]
+    async fn workspace_symbols() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut service, socket) =
+            LspService::new(|client| Backend::new(client, "tremor-script", module_path()));
+        let indexed = indexing(socket);
+
+        let folder = dir.path().to_path_buf();
+        std::fs::create_dir_all(&folder).unwrap();
+        let flows = folder.join("flows.troy");
+        let lib = folder.join("lib.tremor");
+        std::fs::write(
+            &flows,
+            "define flow crawl\nflow\n  define connector out from stdio;\n  define pipeline relay\n  pipeline\n    select event from in into out;\n  end;\n  create connector out;\n  create pipeline relay;\n  connect /pipeline/relay to /connector/out;\nend;\ndeploy flow crawl;\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &lib,
+            "const CRAWL_LIMIT = 42;\nfn crawler() with\n  CRAWL_LIMIT\nend;\n",
+        )
+        .unwrap();
+        let folder = Url::from_directory_path(folder).unwrap();
+        initialize_with(
+            &mut service,
+            json!({
+                "capabilities": {},
+                "workspaceFolders": [{"uri": folder, "name": "symbols"}]
+            }),
+        )
+        .await;
+        indexed.recv().await.unwrap();
+
+        let search = |symbols: &Value| -> Value {
+            symbols
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|symbol| json!([symbol["name"], symbol["kind"], symbol["containerName"]]))
+                .collect()
+        };
+        let symbols = request(&mut service, "workspace/symbol", json!({"query": "cra"})).await;
+        assert_eq!(
+            json!([
+                ["crawl", 3, null],
+                ["crawler", 12, null],
+                ["CRAWL_LIMIT", 14, null]
+            ]),
+            search(&symbols)
+        );
+        let symbols = request(&mut service, "workspace/symbol", json!({"query": "lim"})).await;
+        assert_eq!(json!([["CRAWL_LIMIT", 14, null]]), search(&symbols));
+        let symbols = request(&mut service, "workspace/symbol", json!({"query": "rly"})).await;
+        assert_eq!(json!([["relay", 5, "crawl"]]), search(&symbols));
+        assert_eq!(
+            json!({
+                "uri": Url::from_file_path(&flows).unwrap(),
+                "range": {
+                    "start": {"line": 3, "character": 18},
+                    "end": {"line": 3, "character": 23}
                 }
-            }}}))
-            .id(1)
-            .finish();
+            }),
+            symbols[0]["location"]
+        );
 
-        let _initialize_res = service
-            .call(initialize_req)
-            .await
-            .expect("Expect request to be executed");
+        // edits of open documents are indexed right away
+        let lib = Url::from_file_path(lib).unwrap();
+        open(
+            &mut service,
+            lib.as_str(),
+            "fn question() with\n  42\nend;\n",
+        )
+        .await;
+        let symbols = request(&mut service, "workspace/symbol", json!({"query": "lim"})).await;
+        assert_eq!(json!([]), search(&symbols));
+        let symbols = request(&mut service, "workspace/symbol", json!({"query": "qst"})).await;
+        assert_eq!(json!([["question", 12, null]]), search(&symbols));
+    }
 
-        let initialized_req = Request::build("initialized").params(json!({})).finish();
-        let _initialized_res = service
-            .call(initialized_req)
-            .await
-            .expect("Expect request to be executed");
+    #[async_std::test]
+    async fn indexed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut service, socket) =
+            LspService::new(|client| Backend::new(client, "tremor-script", module_path()));
+        let indexed = indexing(socket);
 
-        let req = Request::build("textDocument/didOpen")
-            .params(json!({"textDocument": {
-                "uri": format!("file://{}/{}", env!("CARGO_MANIFEST_DIR"), "tests/warning_class.tremor"),
-                "languageId": "tremor-deploy",
-                "version": 1,
-                "text": ""
-            }}))
-            .finish();
-        let _res = service
-            .call(req)
-            .await
-            .expect("Expect request to be executed");
+        let folder = dir.path().to_path_buf();
+        for (subfolder, function) in [
+            ("src", "found"),
+            (".git", "fetched"),
+            ("target", "fixed"),
+            ("node_modules", "fresh"),
+        ] {
+            std::fs::create_dir_all(folder.join(subfolder)).unwrap();
+            std::fs::write(
+                folder.join(subfolder).join("lib.tremor"),
+                format!("fn {function}() with\n  42\nend;\n"),
+            )
+            .unwrap();
+        }
+        // a link back to the folder itself is only walked once
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&folder, folder.join("src/loop")).unwrap();
+        let folder = Url::from_directory_path(folder).unwrap();
+        initialize_with(
+            &mut service,
+            json!({
+                "capabilities": {},
+                "workspaceFolders": [{"uri": folder, "name": "indexed"}]
+            }),
+        )
+        .await;
+        indexed.recv().await.unwrap();
 
-        let result = join_handle.timeout(std::time::Duration::from_secs(5)).await;
+        let symbols = request(&mut service, "workspace/symbol", json!({"query": "f"})).await;
+        let names: Vec<&Value> = symbols
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|symbol| {
+                symbol["location"]["uri"]
+                    .as_str()
+                    .map_or(false, |uri| uri.starts_with(folder.as_str()))
+            })
+            .map(|symbol| &symbol["name"])
+            .collect();
+        assert_eq!(vec![&json!("found")], names);
+    }
+
+    #[async_std::test]
+    async fn document_highlights() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut service, mut socket) =
+            LspService::new(|client| Backend::new(client, "tremor-script", module_path()));
+        async_std::task::spawn(async move { while socket.next().await.is_some() {} });
+        initialize(&mut service).await;
 
+        let highlights = |highlights: &Value| -> Value {
+            highlights
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|h| {
+                    let range = &h["range"];
+                    json!([
+                        range["start"]["line"],
+                        range["start"]["character"],
+                        range["end"]["character"],
+                        h["kind"]
+                    ])
+                })
+                .collect()
+        };
+        let uri = &file_uri(&dir, "highlights.tremor");
+        open(
+            &mut service,
+            uri,
+            "let a = 1;\nlet a = a + 1;\nfn f(a) with\n  a\nend;\nemit a;\n",
+        )
+        .await;
+        let at = |line, character| {
+            json!({
+                "textDocument": {"uri": uri},
+                "position": {"line": line, "character": character}
+            })
+        };
         assert_eq!(
-            result,
-            Ok(Some(
-                "consistency: const's are canonically written in UPPER_CASE".to_string()
-            ))
+            json!([[0, 4, 5, 3], [1, 4, 5, 3], [1, 8, 9, 2], [5, 5, 6, 2]]),
+            highlights(&request(&mut service, "textDocument/documentHighlight", at(5, 5)).await)
+        );
+        // the argument of `f` shadows the local
+        assert_eq!(
+            json!([[2, 5, 6, 3], [3, 2, 3, 2]]),
+            highlights(&request(&mut service, "textDocument/documentHighlight", at(3, 2)).await)
+        );
+
+        let uri = &file_uri(&dir, "highlights.trickle");
+        open(
+            &mut service,
+            uri,
+            "define window w from tumbling\nwith\n  size = 2\nend;\nselect aggr::stats::count() from in[w] into out;\n",
+        )
+        .await;
+        let at = |line, character| {
+            json!({
+                "textDocument": {"uri": uri},
+                "position": {"line": line, "character": character}
+            })
+        };
+        assert_eq!(
+            json!([[0, 14, 15, 3], [4, 36, 37, 2]]),
+            highlights(&request(&mut service, "textDocument/documentHighlight", at(4, 36)).await)
+        );
+    }
+
+    // decodes semantic tokens into absolute [line, column, length, type, modifiers]
+    fn semantic_tokens(data: &Value) -> Vec<Value> {
+        let legend = semantic_tokens::legend();
+        let data: Vec<u32> = serde_json::from_value(data.clone()).unwrap();
+        let (mut line, mut column) = (0, 0);
+        data.chunks(5)
+            .map(|token| {
+                line += token[0];
+                column = if token[0] == 0 {
+                    column + token[1]
+                } else {
+                    token[1]
+                };
+                let modifiers: Vec<&str> = legend
+                    .token_modifiers
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| token[4] & (1 << i) != 0)
+                    .map(|(_, modifier)| modifier.as_str())
+                    .collect();
+                json!([
+                    line,
+                    column,
+                    token[2],
+                    legend.token_types[token[3] as usize].as_str(),
+                    modifiers.join(" ")
+                ])
+            })
+            .collect()
+    }
+
+    #[async_std::test]
+    async fn semantic_tokens_full_range_and_delta() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut service, mut socket) =
+            LspService::new(|client| Backend::new(client, "tremor-script", module_path()));
+        async_std::task::spawn(async move { while socket.next().await.is_some() {} });
+        initialize(&mut service).await;
+
+        let uri = &file_uri(&dir, "semantic.tremor");
+        open(
+            &mut service,
+            uri,
+            "## the answer\nconst ANSWER = 42;\nfn add(x) with x + ANSWER end;\nlet s = \"#{ event.a } or #{ $b }\";\nemit string::len(s) > 1 and re|a.*|\n",
+        )
+        .await;
+        let full = request(
+            &mut service,
+            "textDocument/semanticTokens/full",
+            json!({"textDocument": {"uri": uri}}),
+        )
+        .await;
+        assert_eq!(json!("1"), full["resultId"]);
+        assert_eq!(
+            vec![
+                json!([0, 0, 13, "comment", "documentation"]),
+                json!([1, 0, 5, "keyword", ""]),
+                json!([1, 6, 6, "variable", "declaration readonly"]),
+                json!([1, 13, 1, "operator", ""]),
+                json!([1, 15, 2, "number", ""]),
+                json!([2, 0, 2, "keyword", ""]),
+                json!([2, 3, 3, "function", "declaration"]),
+                json!([2, 7, 1, "parameter", "declaration"]),
+                json!([2, 10, 4, "keyword", ""]),
+                json!([2, 15, 1, "parameter", ""]),
+                json!([2, 17, 1, "operator", ""]),
+                json!([2, 19, 6, "variable", "readonly"]),
+                json!([2, 26, 3, "keyword", ""]),
+                json!([3, 0, 3, "keyword", ""]),
+                json!([3, 4, 1, "variable", "declaration"]),
+                json!([3, 6, 1, "operator", ""]),
+                json!([3, 8, 1, "string", ""]),
+                // string interpolation
+                json!([3, 9, 2, "operator", ""]),
+                json!([3, 12, 5, "variable", "defaultLibrary"]),
+                json!([3, 18, 1, "property", ""]),
+                json!([3, 20, 1, "operator", ""]),
+                json!([3, 21, 4, "string", ""]),
+                json!([3, 25, 2, "operator", ""]),
+                json!([3, 28, 1, "variable", "defaultLibrary"]),
+                json!([3, 29, 1, "property", ""]),
+                json!([3, 31, 1, "operator", ""]),
+                json!([3, 32, 1, "string", ""]),
+                json!([4, 0, 4, "keyword", ""]),
+                json!([4, 5, 6, "namespace", ""]),
+                json!([4, 13, 3, "function", ""]),
+                json!([4, 17, 1, "variable", ""]),
+                json!([4, 20, 1, "operator", ""]),
+                json!([4, 22, 1, "number", ""]),
+                json!([4, 24, 3, "operator", ""]),
+                json!([4, 28, 2, "function", "defaultLibrary"]),
+                json!([4, 30, 5, "regexp", ""]),
+            ],
+            semantic_tokens(&full["data"])
+        );
+
+        let range = request(
+            &mut service,
+            "textDocument/semanticTokens/range",
+            json!({
+                "textDocument": {"uri": uri},
+                "range": {
+                    "start": {"line": 1, "character": 7},
+                    "end": {"line": 1, "character": 16}
+                }
+            }),
+        )
+        .await;
+        assert_eq!(
+            vec![
+                json!([1, 6, 6, "variable", "declaration readonly"]),
+                json!([1, 13, 1, "operator", ""]),
+                json!([1, 15, 2, "number", ""]),
+            ],
+            semantic_tokens(&range["data"])
+        );
+
+        // turn the doc comment into a plain comment
+        let change = Request::build("textDocument/didChange")
+            .params(json!({
+                "textDocument": {"uri": uri, "version": 2},
+                "contentChanges": [{
+                    "range": {
+                        "start": {"line": 0, "character": 0},
+                        "end": {"line": 0, "character": 1}
+                    },
+                    "text": ""
+                }]
+            }))
+            .finish();
+        service.call(change).await.unwrap();
+        let delta = request(
+            &mut service,
+            "textDocument/semanticTokens/full/delta",
+            json!({"textDocument": {"uri": uri}, "previousResultId": "1"}),
+        )
+        .await;
+        assert_eq!(
+            json!({
+                "resultId": "2",
+                "edits": [{"start": 0, "deleteCount": 5, "data": [0, 0, 12, 5, 0]}]
+            }),
+            delta
+        );
+        // unknown result ids get all tokens
+        let delta = request(
+            &mut service,
+            "textDocument/semanticTokens/full/delta",
+            json!({"textDocument": {"uri": uri}, "previousResultId": "0"}),
+        )
+        .await;
+        assert_eq!(json!("2"), delta["resultId"]);
+        assert_eq!(
+            full["data"].as_array().unwrap().len(),
+            delta["data"].as_array().unwrap().len()
         );
     }
 }
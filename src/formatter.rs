@@ -0,0 +1,290 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Formatting of tremor documents from their tokens, so documents that don't
+// parse are formatted too. Line breaks are kept as written: lines are indented
+// by how deeply they are nested in brackets and blocks, and the spacing
+// between the tokens of a line is normalized. Comments are kept, as are the
+// lines of heredocs, which are part of their strings.
+
+use crate::language::{is_trivia, Token, TokenSpan};
+use crate::lsp_utils;
+use std::ops::RangeInclusive;
+use tower_lsp::lsp_types::TextEdit;
+
+// what a line can be nested in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Frame {
+    /// `(`, `[`, `{` and their pattern and interpolation variants
+    Bracket,
+    /// the cases or operations of a `match`, `for`, `patch` or `merge`, or
+    /// the cases of a `fn`
+    Block { cases: bool },
+    /// a `case` of a block with cases, up to the next case or `end`
+    Case,
+    /// the config of a `with`, the arguments of an `args` or a named script,
+    /// which the body of a definition follows
+    Header,
+    /// the body of a definition: `script`, `pipeline` or `flow`
+    Body,
+    /// an expression continued on the next line, up to the `;`
+    Continuation,
+}
+
+// the frames a line is nested in, with the lines they were opened on
+#[derive(Default)]
+struct Nesting {
+    frames: Vec<(Frame, usize)>,
+    // the keywords of the constructs whose `of` is yet to come
+    constructs: Vec<Token<'static>>,
+}
+
+impl Nesting {
+    // how many levels a line is indented: frames opened on the same line
+    // count once
+    fn depth(&self) -> usize {
+        let mut lines: Vec<usize> = self.frames.iter().map(|(_, line)| *line).collect();
+        lines.dedup();
+        lines.len()
+    }
+
+    fn top(&self) -> Option<Frame> {
+        self.frames.last().map(|(frame, _)| *frame)
+    }
+
+    fn pop_while(&mut self, f: impl Fn(Frame) -> bool) {
+        while self.top().map_or(false, &f) {
+            self.frames.pop();
+        }
+    }
+
+    // the frames `token` closes
+    fn close(&mut self, token: &Token, previous: Option<&Token>) {
+        match token {
+            Token::RParen | Token::RBracket | Token::RBrace => {
+                if let Some(i) = self.frames.iter().rposition(|(f, _)| *f == Frame::Bracket) {
+                    self.frames.truncate(i);
+                }
+            }
+            Token::End => {
+                self.pop_while(|f| matches!(f, Frame::Continuation | Frame::Case));
+                if matches!(
+                    self.top(),
+                    Some(Frame::Block { .. } | Frame::Header | Frame::Body)
+                ) {
+                    self.frames.pop();
+                }
+            }
+            Token::Case | Token::Default => {
+                self.pop_while(|f| matches!(f, Frame::Continuation | Frame::Case));
+            }
+            Token::Semi => self.pop_while(|f| f == Frame::Continuation),
+            _ if is_body(token, previous) => {
+                self.pop_while(|f| f == Frame::Continuation);
+                if self.top() == Some(Frame::Header) {
+                    self.frames.pop();
+                }
+            }
+            _ => (),
+        }
+    }
+
+    // the frames `token` opens, on `line`
+    fn open(&mut self, token: &Token, previous: Option<&Token>, next: Option<&Token>, line: usize) {
+        let frame = match token {
+            Token::LParen
+            | Token::LBracket
+            | Token::LBrace
+            | Token::LPatParen
+            | Token::LPatBracket
+            | Token::LPatBrace
+            | Token::Interpol => Frame::Bracket,
+            Token::Match => return self.constructs.push(Token::Match),
+            Token::For => return self.constructs.push(Token::For),
+            Token::Fun => return self.constructs.push(Token::Fun),
+            Token::Patch => return self.constructs.push(Token::Patch),
+            Token::Merge => return self.constructs.push(Token::Merge),
+            Token::Of => Frame::Block {
+                cases: matches!(
+                    self.constructs.pop(),
+                    Some(Token::Match | Token::For | Token::Fun)
+                ),
+            },
+            Token::Case | Token::Default => match self.top() {
+                Some(Frame::Block { cases: true }) => Frame::Case,
+                _ => return,
+            },
+            Token::With => {
+                if self.constructs.last() == Some(&Token::Fun) {
+                    self.constructs.pop();
+                }
+                Frame::Header
+            }
+            // `args` followed by names, rather than a path like `args.x`
+            Token::Args if matches!(next, Some(Token::Ident(..))) => Frame::Header,
+            // named scripts: `script from name`
+            Token::Script if next == Some(&Token::From) => Frame::Header,
+            _ if is_body(token, previous) => Frame::Body,
+            _ => return,
+        };
+        self.frames.push((frame, line));
+    }
+}
+
+// whether `token` starts the body of a definition, rather than naming the
+// kind of a definition, like `define script`, or of a deploy endpoint, like
+// `/pipeline/`
+fn is_body(token: &Token, previous: Option<&Token>) -> bool {
+    matches!(token, Token::Script | Token::Pipeline | Token::Flow)
+        && !matches!(
+            previous,
+            Some(Token::Define | Token::Create | Token::Deploy | Token::Div)
+        )
+}
+
+fn is_closer(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::RParen | Token::RBracket | Token::RBrace | Token::End
+    )
+}
+
+/// The lines of `text` formatted, one for each line of `text` (split at
+/// `\n`), indented by `unit` per level
+pub(crate) fn format(text: &str, tokens: &[TokenSpan], unit: &str) -> Vec<String> {
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let line_of = |offset: usize| line_starts.partition_point(|start| *start <= offset) - 1;
+    let mut lines: Vec<Vec<&TokenSpan>> = vec![Vec::new(); line_starts.len()];
+    // lines within heredocs are kept as they are
+    let mut verbatim = vec![false; line_starts.len()];
+    let mut heredoc = None;
+    for token in tokens {
+        let start = token.span.start().absolute();
+        if token.value == Token::NewLine || start > text.len() {
+            continue;
+        }
+        let line = line_of(start);
+        match token.value {
+            Token::HereDocStart => heredoc = Some(line),
+            Token::HereDocEnd => {
+                for v in &mut verbatim[heredoc.map_or(line, |l| l + 1)..=line] {
+                    *v = true;
+                }
+                heredoc = None;
+            }
+            _ => (),
+        }
+        lines[line].push(token);
+    }
+
+    let significant: Vec<&Token> = tokens
+        .iter()
+        .map(|t| &t.value)
+        .filter(|t| !is_trivia(t))
+        .collect();
+    let mut k = 0;
+    let mut nesting = Nesting::default();
+    let mut formatted = Vec::with_capacity(lines.len());
+    for (i, line) in lines.iter().enumerate() {
+        let end = line_starts.get(i + 1).map_or(text.len(), |next| next - 1);
+        let original = &text[line_starts[i]..end];
+        let count = line.iter().filter(|t| !is_trivia(&t.value)).count();
+        // closing tokens at the start of the line dedent the line itself
+        let mut depth = None;
+        for n in k..k + count {
+            let (token, previous) = (significant[n], n.checked_sub(1).map(|p| significant[p]));
+            nesting.close(token, previous);
+            if depth.is_none() && !is_closer(token) {
+                depth = Some(nesting.depth());
+            }
+            nesting.open(token, previous, significant.get(n + 1).copied(), i);
+        }
+        let depth = depth.unwrap_or_else(|| nesting.depth());
+        k += count;
+        if count > 0 && significant[k - 1] == &Token::Eq {
+            nesting.frames.push((Frame::Continuation, i));
+        }
+
+        if verbatim[i] {
+            formatted.push(original.to_string());
+        } else {
+            formatted.push(format_line(text, line, &unit.repeat(depth), original));
+        }
+    }
+    formatted
+}
+
+// a line indented by `indent`, with the whitespace between its tokens
+// normalized: a single space, none before `,` and `;`, and one after `,`
+fn format_line(text: &str, tokens: &[&TokenSpan], indent: &str, original: &str) -> String {
+    let cr = if original.ends_with('\r') { "\r" } else { "" };
+    let Some(first) = tokens
+        .iter()
+        .position(|t| !matches!(t.value, Token::Whitespace(_)))
+    else {
+        return cr.to_string();
+    };
+    let last = tokens
+        .iter()
+        .rposition(|t| !matches!(t.value, Token::Whitespace(_)))
+        .unwrap_or(first);
+    let mut line = indent.to_string();
+    let mut at = tokens[first].span.start().absolute();
+    for (n, token) in tokens.iter().enumerate().take(last + 1).skip(first) {
+        let (start, end) = (token.span.start().absolute(), token.span.end().absolute());
+        if let Token::Whitespace(_) = token.value {
+            line.push_str(&text[at..start]);
+            if !matches!(
+                tokens.get(n + 1).map(|t| &t.value),
+                Some(Token::Comma | Token::Semi)
+            ) {
+                line.push(' ');
+            }
+        } else {
+            line.push_str(&text[at..end]);
+            if token.value == Token::Comma
+                && n < last
+                && !matches!(tokens[n + 1].value, Token::Whitespace(_))
+            {
+                line.push(' ');
+            }
+        }
+        at = end;
+    }
+    line.push_str(cr);
+    line
+}
+
+/// Edits formatting the `lines` of `text`, one for each line that changes
+pub(crate) fn edits(
+    text: &str,
+    tokens: &[TokenSpan],
+    unit: &str,
+    lines: RangeInclusive<usize>,
+) -> Vec<TextEdit> {
+    let mut start = 0;
+    let mut edits = Vec::new();
+    for (i, (original, formatted)) in text.split('\n').zip(format(text, tokens, unit)).enumerate() {
+        if lines.contains(&i) && original != formatted {
+            edits.push(TextEdit::new(
+                lsp_utils::to_lsp_range(text, &(start..start + original.len())),
+                formatted,
+            ));
+        }
+        start += original.len() + 1;
+    }
+    edits
+}
@@ -16,15 +16,22 @@
 mod prelude;
 mod deploy;
 mod query;
+mod recovery;
 mod script;
+mod symbols;
 
 pub(crate) use tremor_script::highlighter::ErrorLevel;
 pub(crate) use tremor_script::pos::Location;
 
 // common language trait
-pub(crate) use prelude::{Language, Token, TokenSpan};
+pub(crate) use prelude::{Language, Problem, SymbolTable, Token, TokenSpan};
+pub(crate) use symbols::{
+    assigned, call_at, doc_comment, first_declaration, implicit_names, is_trivia, parameters,
+    use_insertion, Assigned, Kind, Outline, Path,
+};
 
 use self::{deploy::TremorDeploy, query::TremorQuery, script::TremorScript};
+use tower_lsp::lsp_types::Url;
 
 pub(crate) const LANGUAGE_NAMES: &[&str] = &[
     script::LANGUAGE_NAME,
@@ -33,16 +40,54 @@ pub(crate) const LANGUAGE_NAMES: &[&str] = &[
     // alternate names for above
     script::FILE_EXTENSION,
     query::FILE_EXTENSION,
-    deploy::LANGUAGE_NAME,
+    deploy::FILE_EXTENSION,
+];
+
+/// Extensions of tremor files, in the order modules are resolved in
+pub(crate) const FILE_EXTENSIONS: &[&str] = &[
+    script::FILE_EXTENSION,
+    query::FILE_EXTENSION,
+    deploy::FILE_EXTENSION,
 ];
 
 pub(crate) const DEFAULT_LANGUAGE_NAME: &str = script::LANGUAGE_NAME;
 
-pub(crate) fn lookup(language_name: &str) -> Option<Box<dyn Language>> {
+/// Canonical language name for a language name or one of its alternate names
+pub(crate) fn canonical_name(language_name: &str) -> Option<&'static str> {
     match language_name {
-        script::LANGUAGE_NAME | script::FILE_EXTENSION => Some(Box::<TremorScript>::default()),
-        query::LANGUAGE_NAME | query::FILE_EXTENSION => Some(Box::<TremorQuery>::default()),
-        deploy::LANGUAGE_NAME | deploy::FILE_EXTENSION => Some(Box::<TremorDeploy>::default()),
+        script::LANGUAGE_NAME | script::FILE_EXTENSION => Some(script::LANGUAGE_NAME),
+        query::LANGUAGE_NAME | query::FILE_EXTENSION => Some(query::LANGUAGE_NAME),
+        deploy::LANGUAGE_NAME | deploy::FILE_EXTENSION => Some(deploy::LANGUAGE_NAME),
         _ => None,
     }
 }
+
+/// Detects the language of a document, from the language id sent by the client
+/// or, if that isn't a tremor language, from the extension of its uri
+pub(crate) fn detect(language_id: &str, uri: &Url) -> Option<&'static str> {
+    canonical_name(language_id).or_else(|| {
+        uri.path()
+            .rsplit_once('.')
+            .and_then(|(_, extension)| canonical_name(extension))
+    })
+}
+
+pub(crate) fn lookup(language_name: &str) -> Option<Box<dyn Language>> {
+    match canonical_name(language_name)? {
+        script::LANGUAGE_NAME => Some(Box::<TremorScript>::default()),
+        query::LANGUAGE_NAME => Some(Box::<TremorQuery>::default()),
+        deploy::LANGUAGE_NAME => Some(Box::<TremorDeploy>::default()),
+        _ => None,
+    }
+}
+
+/// All supported languages, keyed by their canonical name
+pub(crate) fn all() -> impl Iterator<Item = (&'static str, Box<dyn Language>)> {
+    [
+        script::LANGUAGE_NAME,
+        query::LANGUAGE_NAME,
+        deploy::LANGUAGE_NAME,
+    ]
+    .into_iter()
+    .filter_map(|name| Some((name, lookup(name)?)))
+}
@@ -13,9 +13,11 @@
 // limitations under the License.
 
 use crate::language::prelude::*;
+use crate::language::{recovery, symbols};
 
 pub(crate) const LANGUAGE_NAME: &str = "tremor-deploy";
 pub(crate) const FILE_EXTENSION: &str = "troy";
+const DOCS_URL: &str = "https://www.tremor.rs/docs/0.12/language/deploy";
 
 #[derive(Debug)]
 pub(crate) struct TremorDeploy {
@@ -33,19 +35,50 @@ impl Default for TremorDeploy {
 }
 
 impl Language for TremorDeploy {
-    fn parse_errors(&self, _uri: &Url, text: &str) -> Option<Vec<Error>> {
-        // FIXME .unwrap() should we path in something here?
+    fn functions(&self, _uri: &Url, module_name: &str) -> Vec<String> {
+        registry_functions(&self.registry, module_name)
+    }
+
+    fn function_arity(&self, module_name: &str, name: &str) -> Option<RangeInclusive<usize>> {
+        registry_arity(&self.registry, module_name, name)
+    }
+
+    fn docs_url(&self) -> &'static str {
+        DOCS_URL
+    }
 
+    fn parse(&self, text: &str) -> std::result::Result<Vec<Problem>, ScriptError> {
         match Deploy::parse_with_aid(text, &self.registry, &self.aggr_registry) {
             Ok(deploy) => {
-                let r = Some(deploy.warnings.iter().map(Into::into).collect());
+                let r = deploy.warnings.iter().map(Into::into).collect();
                 unsafe { deploy.consume_and_free().unwrap() };
-                r
+                Ok(r)
             }
             Err(tremor_script::errors::ErrorWithIndex(aid, e)) => {
-                let r = Some(vec![(&e).into()]);
                 unsafe { Arena::delte_index_this_is_really_unsafe_dont_use_it(aid).unwrap() };
-                r
+                Err(e)
+            }
+        }
+    }
+
+    fn parse_errors(&self, _uri: &Url, text: &str) -> Vec<Problem> {
+        recovery::all_errors(text, |text| self.parse(text))
+    }
+
+    fn definitions(&self, _uri: &Url, text: &str) -> Option<Vec<Definition>> {
+        match Deploy::parse_with_aid(text, &self.registry, &self.aggr_registry) {
+            Ok(deploy) => {
+                let r = symbols::deploy_definitions(
+                    &deploy.deploy.stmts,
+                    &deploy.deploy.scope.content,
+                    deploy.aid,
+                );
+                unsafe { deploy.consume_and_free().unwrap() };
+                Some(r)
+            }
+            Err(tremor_script::errors::ErrorWithIndex(aid, _)) => {
+                unsafe { Arena::delte_index_this_is_really_unsafe_dont_use_it(aid).unwrap() };
+                None
             }
         }
     }
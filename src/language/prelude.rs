@@ -12,18 +12,212 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub(crate) use std::ops::RangeInclusive;
 pub(crate) use tower_lsp::lsp_types::Url;
 pub(crate) use tremor_script::arena::{self, Arena};
+use tremor_script::ast::warning::{Class, Warning};
 pub(crate) use tremor_script::deploy::Deploy;
 pub(crate) use tremor_script::docs::FunctionDoc;
-use tremor_script::errors::Result;
+pub(crate) use tremor_script::errors::Error as ScriptError;
+use tremor_script::errors::{ErrorKind, Result};
 pub(crate) use tremor_script::highlighter::Error;
 pub(crate) use tremor_script::registry;
 
 pub(crate) use tremor_script::lexer::{Lexer, Token, TokenSpan};
 
+use crate::language::symbols::token_definitions;
+pub(crate) use crate::language::symbols::{Definition, SymbolTable};
+
+/// An error or warning found in a document, with a stable code for its kind
+#[derive(Debug)]
+pub(crate) struct Problem {
+    pub(crate) error: Error,
+    /// the kind of error, like `unrecognized-token`, or the class of warning,
+    /// like `consistency`
+    pub(crate) code: String,
+}
+
+impl Problem {
+    pub(crate) const DUPLICATE_DEFINITION: &'static str = "duplicate-definition";
+    pub(crate) const MISSING_FUNCTION: &'static str = "missing-function";
+    pub(crate) const UNRECOGNIZED_TOKEN: &'static str = "unrecognized-token";
+    pub(crate) const CONSISTENCY: &'static str = "consistency";
+
+    /// Name of what was defined twice, for duplicate definitions
+    pub(crate) fn duplicate(&self) -> Option<&str> {
+        if self.code != Self::DUPLICATE_DEFINITION {
+            return None;
+        }
+        let (_, rest) = self.error.callout().split_once('`')?;
+        rest.rsplit_once("` twice").map(|(name, _)| name)
+    }
+}
+
+impl From<&ScriptError> for Problem {
+    fn from(e: &ScriptError) -> Self {
+        let error = Error::from(e);
+        let code = match e.kind() {
+            ErrorKind::Generic(..) if error.callout().ends_with("twice") => {
+                Self::DUPLICATE_DEFINITION
+            }
+            kind => error_code(kind),
+        };
+        Self {
+            error,
+            code: code.to_string(),
+        }
+    }
+}
+
+// the code of a kind of error, spelled out for each kind so the codes clients
+// and quick fixes go by stay the same when tremor renames its error kinds
+#[allow(clippy::too_many_lines)]
+fn error_code(kind: &ErrorKind) -> &'static str {
+    match kind {
+        ErrorKind::DoubleConst { .. }
+        | ErrorKind::DoubleStream { .. }
+        | ErrorKind::DoublePipelineCreate { .. } => Problem::DUPLICATE_DEFINITION,
+        ErrorKind::UnrecognizedToken { .. } => Problem::UNRECOGNIZED_TOKEN,
+        ErrorKind::ExtraToken { .. } => "extra-token",
+        ErrorKind::InvalidToken { .. } => "invalid-token",
+        ErrorKind::InvalidPP { .. } => "invalid-pp",
+        ErrorKind::Generic { .. } => "generic",
+        ErrorKind::CyclicUse { .. } => "cyclic-use",
+        ErrorKind::TypeError { .. } => "type-error",
+        ErrorKind::EmptyScript { .. } => "empty-script",
+        ErrorKind::NotConstant { .. } => "not-constant",
+        ErrorKind::TypeConflict { .. } => "type-conflict",
+        ErrorKind::Oops { .. } => "oops",
+        ErrorKind::NotFound { .. } => "not-found",
+        ErrorKind::BadArity { .. } => "bad-arity",
+        ErrorKind::MissingModule { .. } => "missing-module",
+        ErrorKind::MissingFunction { .. } => Problem::MISSING_FUNCTION,
+        ErrorKind::AggrInAggr { .. } => "aggr-in-aggr",
+        ErrorKind::BadType { .. } => "bad-type",
+        ErrorKind::RuntimeError { .. } => "runtime-error",
+        ErrorKind::InvalidRecur { .. } => "invalid-recur",
+        ErrorKind::RecursionLimit { .. } => "recursion-limit",
+        ErrorKind::UnterminatedExtractor { .. } => "unterminated-extractor",
+        ErrorKind::UnterminatedStringLiteral { .. } => "unterminated-string-literal",
+        ErrorKind::UnterminatedHereDoc { .. } => "unterminated-here-doc",
+        ErrorKind::TailingHereDoc { .. } => "tailing-here-doc",
+        ErrorKind::UnterminatedInterpolation { .. } => "unterminated-interpolation",
+        ErrorKind::EmptyInterpolation { .. } => "empty-interpolation",
+        ErrorKind::UnterminatedIdentLiteral { .. } => "unterminated-ident-literal",
+        ErrorKind::UnexpectedCharacter { .. } => "unexpected-character",
+        ErrorKind::UnexpectedEscapeCode { .. } => "unexpected-escape-code",
+        ErrorKind::InvalidHexLiteral { .. } => "invalid-hex-literal",
+        ErrorKind::InvalidIntLiteral { .. } => "invalid-int-literal",
+        ErrorKind::InvalidFloatLiteral { .. } => "invalid-float-literal",
+        ErrorKind::UnexpectedEndOfStream { .. } => "unexpected-end-of-stream",
+        ErrorKind::ModuleNotFound { .. } => "module-not-found",
+        ErrorKind::ParserError { .. } => "parser-error",
+        ErrorKind::UnknownLocal { .. } => "unknown-local",
+        ErrorKind::BadAccessInLocal { .. } => "bad-access-in-local",
+        ErrorKind::BadAccessInGlobal { .. } => "bad-access-in-global",
+        ErrorKind::BadAccessInEvent { .. } => "bad-access-in-event",
+        ErrorKind::BadAccessInState { .. } => "bad-access-in-state",
+        ErrorKind::BadArrayIndex { .. } => "bad-array-index",
+        ErrorKind::DecreasingRange { .. } => "decreasing-range",
+        ErrorKind::ArrayOutOfRange { .. } => "array-out-of-range",
+        ErrorKind::AssignIntoArray { .. } => "assign-into-array",
+        ErrorKind::InvalidAssign { .. } => "invalid-assign",
+        ErrorKind::InvalidConst { .. } => "invalid-const",
+        ErrorKind::InvalidFn { .. } => "invalid-fn",
+        ErrorKind::AssignToConst { .. } => "assign-to-const",
+        ErrorKind::InvalidEmit { .. } => "invalid-emit",
+        ErrorKind::InvalidDrop { .. } => "invalid-drop",
+        ErrorKind::BinaryEmit { .. } => "binary-emit",
+        ErrorKind::BinaryDrop { .. } => "binary-drop",
+        ErrorKind::InvalidUnary { .. } => "invalid-unary",
+        ErrorKind::InvalidBinary { .. } => "invalid-binary",
+        ErrorKind::DivisionByZero { .. } => "division-by-zero",
+        ErrorKind::Overflow { .. } => "overflow",
+        ErrorKind::InvalidBinaryBoolean { .. } => "invalid-binary-boolean",
+        ErrorKind::InvalidBitshift { .. } => "invalid-bitshift",
+        ErrorKind::InvalidExtractor { .. } => "invalid-extractor",
+        ErrorKind::NoClauseHit { .. } => "no-clause-hit",
+        ErrorKind::MissingEffectors { .. } => "missing-effectors",
+        ErrorKind::PatchKeyExists { .. } => "patch-key-exists",
+        ErrorKind::UpdateKeyMissing { .. } => "update-key-missing",
+        ErrorKind::MergeTypeConflict { .. } => "merge-type-conflict",
+        ErrorKind::QueryStreamNotDefined { .. } => "query-stream-not-defined",
+        ErrorKind::NoLocalsAllowed { .. } => "no-locals-allowed",
+        ErrorKind::NoConstsAllowed { .. } => "no-consts-allowed",
+        ErrorKind::NoEventReferencesAllowed { .. } => "no-event-references-allowed",
+        ErrorKind::CantSetWindowConst { .. } => "cant-set-window-const",
+        ErrorKind::CantSetGroupConst { .. } => "cant-set-group-const",
+        ErrorKind::CantSetArgsConst { .. } => "cant-set-args-const",
+        ErrorKind::QueryNodeReservedName { .. } => "query-node-reserved-name",
+        ErrorKind::QueryNodeDuplicateName { .. } => "query-node-duplicate-name",
+        ErrorKind::PipelineUnknownPort { .. } => "pipeline-unknown-port",
+        ErrorKind::DeployArtefactNotDefined { .. } => "deploy-artefact-not-defined",
+        ErrorKind::WithParamNoArg { .. } => "with-param-no-arg",
+        ErrorKind::DeployRequiredArgDoesNotResolve { .. } => "deploy-required-arg-does-not-resolve",
+        ErrorKind::InvalidDefinitionalWithParam { .. } => "invalid-definitional-with-param",
+        ErrorKind::Grok { .. } => "grok",
+        ErrorKind::Io { .. } => "io",
+        ErrorKind::JsonError { .. } => "json-error",
+        ErrorKind::ValueError { .. } => "value-error",
+        ErrorKind::ParseIntError { .. } => "parse-int-error",
+        ErrorKind::Utf8Error { .. } => "utf8-error",
+        ErrorKind::FromUtf8Error { .. } => "from-utf8-error",
+        ErrorKind::NoObjectError { .. } => "no-object-error",
+        ErrorKind::AccessError { .. } => "access-error",
+        ErrorKind::CodecError { .. } => "codec-error",
+        ErrorKind::Common { .. } => "common",
+        ErrorKind::Msg { .. } => "msg",
+        _ => "error",
+    }
+}
+
+impl From<&Warning> for Problem {
+    fn from(w: &Warning) -> Self {
+        Self {
+            error: Error::from(w),
+            code: match w.class {
+                Class::General => "general",
+                Class::Performance => "performance",
+                Class::Consistency => Self::CONSISTENCY,
+                Class::Behaviour => "behaviour",
+            }
+            .to_string(),
+        }
+    }
+}
+
+/// Names of the functions of `module` in `registry`, sorted
+pub(crate) fn registry_functions(registry: &registry::Registry, module: &str) -> Vec<String> {
+    let mut functions: Vec<String> = registry
+        .find_module(module)
+        .map(|functions| functions.keys().cloned().collect())
+        .unwrap_or_default();
+    functions.sort();
+    functions
+}
+
+/// The number of arguments a function of the `registry` takes
+pub(crate) fn registry_arity(
+    registry: &registry::Registry,
+    module: &str,
+    name: &str,
+) -> Option<RangeInclusive<usize>> {
+    registry
+        .find(module, name)
+        .ok()
+        .map(registry::TremorFnWrapper::arity)
+}
+
 pub(crate) trait Language: Send + Sync {
-    fn parse_errors(&self, uri: &Url, text: &str) -> Option<Vec<Error>>;
+    /// Parses a document, returning its warnings or else the first error in it
+    fn parse(&self, text: &str) -> std::result::Result<Vec<Problem>, ScriptError>;
+
+    /// All the errors in a document along with its warnings, none if it is
+    /// fine
+    fn parse_errors(&self, uri: &Url, text: &str) -> Vec<Problem>;
+
+    /// Documentation of the language, which diagnostics link to
+    fn docs_url(&self) -> &'static str;
 
     fn functions(&self, _uri: &Url, _module_name: &str) -> Vec<String> {
         vec![]
@@ -33,6 +227,42 @@ pub(crate) trait Language: Send + Sync {
         None
     }
 
+    /// The number of arguments a function of a module of the registry takes
+    fn function_arity(&self, _module_name: &str, _name: &str) -> Option<RangeInclusive<usize>> {
+        None
+    }
+
+    /// Named definitions in the AST of the document, if it can be parsed
+    fn definitions(&self, _uri: &Url, _text: &str) -> Option<Vec<Definition>> {
+        None
+    }
+
+    /// The tokens the grammar expects at the end of `text`, quoted as in the
+    /// errors of the parser, or `None` if it doesn't parse up to there
+    fn expected_tokens(&self, text: &str) -> Option<Vec<String>> {
+        // a backslash is a token, but one the grammar never expects
+        let probe = format!("{text} \\");
+        match self.parse(&probe).err()?.kind() {
+            ErrorKind::UnrecognizedToken(_, at, token, expected)
+                if token == "\\" && at.start().absolute() == text.len() + 1 =>
+            {
+                Some(expected.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn symbols(&self, uri: &Url, text: &str) -> Option<SymbolTable> {
+        let (aid, tokens) = self.tokenize(uri, text).ok()?;
+        let definitions = self
+            .definitions(uri, text)
+            .unwrap_or_else(|| token_definitions(&tokens, text.len()));
+        let table = SymbolTable::new(&tokens, definitions, text.len());
+        drop(tokens);
+        unsafe { Arena::delte_index_this_is_really_unsafe_dont_use_it(aid).unwrap() };
+        Some(table)
+    }
+
     fn tokenize<'input>(
         &self,
         _uri: &Url,
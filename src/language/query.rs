@@ -13,10 +13,12 @@
 // limitations under the License.
 
 use crate::language::prelude::*;
+use crate::language::{recovery, symbols};
 use tremor_script::query::Query;
 
 pub(crate) const LANGUAGE_NAME: &str = "tremor-query";
 pub(crate) const FILE_EXTENSION: &str = "trickle";
+const DOCS_URL: &str = "https://www.tremor.rs/docs/0.12/language/query";
 
 #[derive(Debug)]
 #[allow(clippy::module_name_repetitions)]
@@ -35,17 +37,50 @@ impl Default for TremorQuery {
 }
 
 impl Language for TremorQuery {
-    fn parse_errors(&self, _uri: &Url, text: &str) -> Option<Vec<Error>> {
+    fn functions(&self, _uri: &Url, module_name: &str) -> Vec<String> {
+        registry_functions(&self.registry, module_name)
+    }
+
+    fn function_arity(&self, module_name: &str, name: &str) -> Option<RangeInclusive<usize>> {
+        registry_arity(&self.registry, module_name, name)
+    }
+
+    fn docs_url(&self) -> &'static str {
+        DOCS_URL
+    }
+
+    fn parse(&self, text: &str) -> std::result::Result<Vec<Problem>, ScriptError> {
         match Query::parse_with_aid(text, &self.registry, &self.aggr_registry) {
             Ok(query) => {
-                let r = Some(query.warnings.iter().map(Into::into).collect());
+                let r = query.warnings.iter().map(Into::into).collect();
                 unsafe { query.consume_and_free().unwrap() };
-                r
+                Ok(r)
             }
             Err(tremor_script::errors::ErrorWithIndex(aid, e)) => {
-                let r = Some(vec![(&e).into()]);
                 unsafe { Arena::delte_index_this_is_really_unsafe_dont_use_it(aid).unwrap() };
-                r
+                Err(e)
+            }
+        }
+    }
+
+    fn parse_errors(&self, _uri: &Url, text: &str) -> Vec<Problem> {
+        recovery::all_errors(text, |text| self.parse(text))
+    }
+
+    fn definitions(&self, _uri: &Url, text: &str) -> Option<Vec<Definition>> {
+        match Query::parse_with_aid(text, &self.registry, &self.aggr_registry) {
+            Ok(query) => {
+                let r = symbols::query_definitions(
+                    &query.query.stmts,
+                    &query.query.scope.content,
+                    query.aid,
+                );
+                unsafe { query.consume_and_free().unwrap() };
+                Some(r)
+            }
+            Err(tremor_script::errors::ErrorWithIndex(aid, _)) => {
+                unsafe { Arena::delte_index_this_is_really_unsafe_dont_use_it(aid).unwrap() };
+                None
             }
         }
     }
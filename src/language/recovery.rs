@@ -0,0 +1,143 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The tremor parsers stop at the first error. To report all of them, we parse
+//! over and over again, each time blanking out the top-level statement the
+//! last error was found in, until what is left of the document parses.
+
+use crate::language::prelude::*;
+use std::ops::Range;
+use tremor_script::errors::{Error as ScriptError, ErrorKind};
+
+// tokens starting top-level statements, when found at the start of a line
+fn is_statement_start(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Use
+            | Token::Const
+            | Token::Fun
+            | Token::Intrinsic
+            | Token::Let
+            | Token::Define
+            | Token::Create
+            | Token::Select
+            | Token::Deploy
+            | Token::Emit
+            | Token::Drop
+    )
+}
+
+// offsets at which top-level statements start. statements are only recognized
+// at the start of a line, so that they are found even when the blocks around
+// them are broken
+fn statement_starts(text: &str) -> Vec<usize> {
+    let Ok((aid, text)) = Arena::insert(text) else {
+        return vec![];
+    };
+    let starts = Lexer::new(text, aid)
+        .map_while(std::result::Result::ok)
+        .filter(|t| t.span.start().column() == 1 && is_statement_start(&t.value))
+        .map(|t| t.span.start().absolute())
+        .collect();
+    unsafe { Arena::delte_index_this_is_really_unsafe_dont_use_it(aid).unwrap() };
+    starts
+}
+
+// the statement containing `offset`
+fn statement(starts: &[usize], len: usize, offset: usize) -> Range<usize> {
+    let next = starts.partition_point(|start| *start <= offset);
+    let start = next.checked_sub(1).map_or(0, |i| starts[i]);
+    start..starts.get(next).copied().unwrap_or(len)
+}
+
+// errors in the grammar of a document, as opposed to errors found after it
+// was parsed, like references to unknown functions
+fn is_syntax_error(e: &ScriptError) -> bool {
+    matches!(
+        e.kind(),
+        ErrorKind::UnrecognizedToken(..)
+            | ErrorKind::ExtraToken(..)
+            | ErrorKind::InvalidToken(..)
+            | ErrorKind::ParserError(..)
+            | ErrorKind::UnexpectedEndOfStream(..)
+            | ErrorKind::UnexpectedCharacter(..)
+            | ErrorKind::UnexpectedEscapeCode(..)
+            | ErrorKind::UnterminatedStringLiteral(..)
+            | ErrorKind::UnterminatedHereDoc(..)
+            | ErrorKind::UnterminatedIdentLiteral(..)
+            | ErrorKind::UnterminatedExtractor(..)
+            | ErrorKind::UnterminatedInterpolation(..)
+            | ErrorKind::TailingHereDoc(..)
+            | ErrorKind::InvalidUtf8Sequence(..)
+    )
+}
+
+/// All errors in `text`, found by `parse`, which returns the warnings of the
+/// document when it parses or else the first error in it
+pub(crate) fn all_errors<F>(text: &str, parse: F) -> Vec<Problem>
+where
+    F: Fn(&str) -> std::result::Result<Vec<Problem>, ScriptError>,
+{
+    let starts = statement_starts(text);
+    let mut text = text.to_string();
+    let mut blanked: Vec<Range<usize>> = Vec::new();
+    let mut errors = Vec::new();
+    loop {
+        let e = match parse(&text) {
+            Ok(warnings) => {
+                errors.extend(warnings);
+                break;
+            }
+            Err(e) => e,
+        };
+        let located = e.context().1.is_some();
+        let syntax = is_syntax_error(&e);
+        // once statements are blanked out, anything but a syntax error might
+        // just be caused by that, like references to what was blanked out
+        let trusted = located && syntax;
+        if !blanked.is_empty() && !trusted {
+            break;
+        }
+        let problem = Problem::from(&e);
+        let offset = problem.error.start().absolute().min(text.len());
+        errors.push(problem);
+        if !located {
+            break;
+        }
+        let mut range = statement(&starts, text.len(), offset);
+        // an unexpected start of a statement is down to the statement before
+        // it, like a missing `;` or `end`
+        if syntax && offset == range.start && offset > 0 {
+            range = statement(&starts, text.len(), offset - 1);
+        }
+        if blanked.contains(&range) {
+            break;
+        }
+        // keep line breaks and byte offsets, for the positions of the errors
+        // still to be found
+        let replacement: String = text[range.clone()]
+            .chars()
+            .map(|c| {
+                if c == '\n' {
+                    "\n".to_string()
+                } else {
+                    " ".repeat(c.len_utf8())
+                }
+            })
+            .collect();
+        text.replace_range(range.clone(), &replacement);
+        blanked.push(range);
+    }
+    errors
+}
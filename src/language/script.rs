@@ -13,18 +13,50 @@
 // limitations under the License.
 
 use crate::language::prelude::*;
+use crate::language::{recovery, symbols};
 use tremor_script::{
     arena::Index,
     errors::ErrorWithIndex,
     module::{Id, Module},
+    Script,
 };
 
 pub(crate) const LANGUAGE_NAME: &str = "tremor-script";
 pub(crate) const FILE_EXTENSION: &str = "tremor";
+const DOCS_URL: &str = "https://www.tremor.rs/docs/0.12/language/script";
 
 #[allow(clippy::module_name_repetitions)]
-#[derive(Debug, Default)]
-pub(crate) struct TremorScript {}
+#[derive(Debug)]
+pub(crate) struct TremorScript {
+    registry: registry::Registry,
+}
+
+impl Default for TremorScript {
+    fn default() -> Self {
+        Self {
+            registry: registry::registry(),
+        }
+    }
+}
+
+impl TremorScript {
+    // loading a module drops the warnings found along the way, so they are
+    // taken from parsing it as a script. this covers modules of `use`s, consts
+    // and functions, which is what scripts can hold as well.
+    fn warnings(&self, text: &str) -> Vec<Problem> {
+        match Script::parse_with_aid(text, &self.registry) {
+            Ok(script) => {
+                let r = script.warnings().map(Into::into).collect();
+                unsafe { script.consume_and_free().unwrap() };
+                r
+            }
+            Err(ErrorWithIndex(aid, _)) => {
+                unsafe { Arena::delte_index_this_is_really_unsafe_dont_use_it(aid).unwrap() };
+                vec![]
+            }
+        }
+    }
+}
 
 fn parse_with_aid(src: &str) -> Result<(Module, Index), ErrorWithIndex> {
     let (aid, src) = Arena::insert(src).unwrap();
@@ -35,19 +67,47 @@ fn parse_with_aid(src: &str) -> Result<(Module, Index), ErrorWithIndex> {
         .map(|m| (m, aid))
 }
 impl Language for TremorScript {
-    fn parse_errors(&self, _uri: &Url, text: &str) -> Option<Vec<Error>> {
-        // FIXME .unwrap() should we path in something here?
+    fn functions(&self, _uri: &Url, module_name: &str) -> Vec<String> {
+        registry_functions(&self.registry, module_name)
+    }
+
+    fn function_arity(&self, module_name: &str, name: &str) -> Option<RangeInclusive<usize>> {
+        registry_arity(&self.registry, module_name, name)
+    }
 
+    fn docs_url(&self) -> &'static str {
+        DOCS_URL
+    }
+
+    fn parse(&self, text: &str) -> std::result::Result<Vec<Problem>, ScriptError> {
         match parse_with_aid(text) {
             Ok((module, aid)) => {
                 drop(module);
                 unsafe { Arena::delte_index_this_is_really_unsafe_dont_use_it(aid).unwrap() };
-                None
+                Ok(self.warnings(text))
             }
             Err(ErrorWithIndex(aid, e)) => {
-                let r = Some(vec![(&e).into()]);
                 unsafe { Arena::delte_index_this_is_really_unsafe_dont_use_it(aid).unwrap() };
-                r
+                Err(e)
+            }
+        }
+    }
+
+    fn parse_errors(&self, _uri: &Url, text: &str) -> Vec<Problem> {
+        recovery::all_errors(text, |text| self.parse(text))
+    }
+
+    fn definitions(&self, _uri: &Url, text: &str) -> Option<Vec<Definition>> {
+        match parse_with_aid(text) {
+            Ok((module, aid)) => {
+                let r = symbols::content_definitions(&module.content, aid);
+                drop(module);
+                unsafe { Arena::delte_index_this_is_really_unsafe_dont_use_it(aid).unwrap() };
+                Some(r)
+            }
+            Err(ErrorWithIndex(aid, _)) => {
+                unsafe { Arena::delte_index_this_is_really_unsafe_dont_use_it(aid).unwrap() };
+                None
             }
         }
     }
@@ -0,0 +1,1322 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Symbols are collected in two passes: the named definitions (and their
+// extents) come from the tremor-script AST of a document, while the positions
+// of names, locals and references come from its token stream, resolved against
+// the scopes the AST definitions span. Documents that don't parse get their
+// definitions from the token stream instead, so a single error doesn't take
+// out all navigation.
+
+use std::ops::Range;
+use tremor_script::arena::{self, Arena};
+use tremor_script::ast::base_expr::Ranged;
+use tremor_script::ast::deploy::CreateTargetDefinition;
+use tremor_script::ast::module::Content;
+use tremor_script::ast::{DeployStmt, FlowDefinition, PipelineDefinition, Stmt};
+use tremor_script::lexer::{Lexer, Token, TokenSpan};
+
+/// Kind of a symbol in a tremor document
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Kind {
+    /// a `use`d module (or its alias)
+    Module,
+    Const,
+    Function,
+    /// an argument of a `fn`
+    Argument,
+    /// a `let` binding or a binding in a `match` / `for` case
+    Local,
+    Window,
+    Operator,
+    Script,
+    Pipeline,
+    Connector,
+    Flow,
+    /// a `create stream`
+    Stream,
+    /// an instance created from an operator, script, pipeline or connector
+    /// definition
+    Instance,
+    /// a `select` statement
+    Select,
+    /// a `deploy flow` statement
+    Deployment,
+}
+
+impl Kind {
+    // kinds of symbols whose extent encloses the symbols defined within
+    fn is_container(self) -> bool {
+        !matches!(self, Kind::Module | Kind::Argument | Kind::Local)
+    }
+
+    pub(crate) fn description(self) -> &'static str {
+        match self {
+            Kind::Module => "module",
+            Kind::Const => "const",
+            Kind::Function => "function",
+            Kind::Argument => "argument",
+            Kind::Local => "local",
+            Kind::Window => "window",
+            Kind::Operator => "operator",
+            Kind::Script => "script",
+            Kind::Pipeline => "pipeline",
+            Kind::Connector => "connector",
+            Kind::Flow => "flow",
+            Kind::Stream => "stream",
+            Kind::Instance => "instance",
+            Kind::Select => "select",
+            Kind::Deployment => "deployment",
+        }
+    }
+
+    // kinds of symbols that are only visible after their definition
+    fn is_sequential(self) -> bool {
+        matches!(self, Kind::Module | Kind::Argument | Kind::Local)
+    }
+}
+
+/// A named definition, as found in the AST of a document
+#[derive(Clone, Debug)]
+pub(crate) struct Definition {
+    name: String,
+    kind: Kind,
+    extent: Range<usize>,
+    detail: Option<String>,
+    children: Vec<Definition>,
+}
+
+impl Definition {
+    pub(crate) fn new<T: Ranged>(name: &str, kind: Kind, node: &T) -> Self {
+        Self {
+            name: name.to_string(),
+            kind,
+            extent: node.s().absolute()..node.e().absolute(),
+            detail: None,
+            children: Vec::new(),
+        }
+    }
+
+    // a definition found in the token stream, spanning `extent`
+    fn at(name: &str, kind: Kind, extent: Range<usize>) -> Self {
+        Self {
+            name: name.to_string(),
+            kind,
+            extent,
+            detail: None,
+            children: Vec::new(),
+        }
+    }
+
+    fn with_detail(mut self, detail: String) -> Self {
+        self.detail = Some(detail);
+        self
+    }
+
+    fn with_children(mut self, children: Vec<Definition>) -> Self {
+        self.children = children;
+        self
+    }
+}
+
+fn named<'a, T: Ranged + 'a>(
+    definitions: &mut Vec<Definition>,
+    entities: impl IntoIterator<Item = (&'a String, &'a T)>,
+    kind: Kind,
+    aid: arena::Index,
+) {
+    for (name, entity) in entities {
+        if entity.aid() == aid {
+            definitions.push(Definition::new(name, kind, entity));
+        }
+    }
+}
+
+/// Definitions of the content of a module, or of the scope of a query or
+/// deployment
+pub(crate) fn content_definitions(content: &Content, aid: arena::Index) -> Vec<Definition> {
+    let mut definitions = Vec::new();
+    named(&mut definitions, &content.consts, Kind::Const, aid);
+    named(&mut definitions, &content.functions, Kind::Function, aid);
+    named(&mut definitions, &content.windows, Kind::Window, aid);
+    named(&mut definitions, &content.operators, Kind::Operator, aid);
+    named(&mut definitions, &content.scripts, Kind::Script, aid);
+    named(&mut definitions, &content.connectors, Kind::Connector, aid);
+    for p in content.pipelines.values().filter(|p| p.aid() == aid) {
+        definitions.push(pipeline_definition(p, aid));
+    }
+    for f in content.flows.values().filter(|f| f.aid() == aid) {
+        definitions.push(flow_definition(f, aid));
+    }
+    definitions.sort_by_key(|d| d.extent.start);
+    definitions
+}
+
+/// Definitions from the statements and the scope of a query or of a pipeline
+/// definition
+pub(crate) fn query_definitions(
+    stmts: &[Stmt],
+    content: &Content,
+    aid: arena::Index,
+) -> Vec<Definition> {
+    let definitions = stmts
+        .iter()
+        .filter(|stmt| stmt.aid() == aid)
+        .map(|stmt| match stmt {
+            Stmt::WindowDefinition(w) => Definition::new(&w.id, Kind::Window, w.as_ref()),
+            Stmt::OperatorDefinition(o) => Definition::new(&o.id, Kind::Operator, o)
+                .with_detail(format!("{}::{}", o.kind.module, o.kind.operation)),
+            Stmt::ScriptDefinition(s) => Definition::new(&s.id, Kind::Script, s.as_ref()),
+            Stmt::PipelineDefinition(p) => pipeline_definition(p, aid),
+            Stmt::StreamCreate(s) => Definition::new(&s.id, Kind::Stream, s),
+            Stmt::OperatorCreate(o) => Definition::new(&o.id, Kind::Instance, o),
+            Stmt::ScriptCreate(s) => Definition::new(&s.id, Kind::Instance, s),
+            Stmt::PipelineCreate(p) => Definition::new(&p.alias, Kind::Instance, p),
+            Stmt::SelectStmt(s) => Definition::new("select", Kind::Select, s)
+                .with_detail(format!("from {} into {}", s.stmt.from.0, s.stmt.into.0)),
+        })
+        .collect();
+    merge(definitions, content_definitions(content, aid))
+}
+
+/// Definitions from the statements and the scope of a deployment
+pub(crate) fn deploy_definitions(
+    stmts: &[DeployStmt],
+    content: &Content,
+    aid: arena::Index,
+) -> Vec<Definition> {
+    let definitions = stmts
+        .iter()
+        .filter(|stmt| stmt.aid() == aid)
+        .map(|stmt| match stmt {
+            DeployStmt::FlowDefinition(f) => flow_definition(f, aid),
+            DeployStmt::PipelineDefinition(p) => pipeline_definition(p, aid),
+            DeployStmt::ConnectorDefinition(c) => {
+                Definition::new(&c.id, Kind::Connector, c.as_ref())
+                    .with_detail(c.builtin_kind.clone())
+            }
+            DeployStmt::DeployFlowStmt(d) => {
+                Definition::new(&d.instance_alias, Kind::Deployment, d.as_ref())
+            }
+        })
+        .collect();
+    merge(definitions, content_definitions(content, aid))
+}
+
+/// Definitions scanned from the tokens of a document that can't be parsed.
+/// Each statement ends at the `;` followed by the next statement, or by the
+/// `end` of the pipeline or flow it is in.
+pub(crate) fn token_definitions(tokens: &[TokenSpan], len: usize) -> Vec<Definition> {
+    // closes the innermost open definition at `end`, nesting it in its parent
+    fn close(open: &mut Vec<Definition>, definitions: &mut Vec<Definition>, end: usize) {
+        if let Some(mut definition) = open.pop() {
+            definition.extent.end = end;
+            match open.last_mut() {
+                Some(parent) => parent.children.push(definition),
+                None => definitions.push(definition),
+            }
+        }
+    }
+
+    let tokens: Vec<&TokenSpan> = tokens.iter().filter(|t| !is_trivia(&t.value)).collect();
+    let mut definitions = Vec::new();
+    let mut open: Vec<Definition> = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let start = token.span.start().absolute();
+        let next = tokens.get(i + 1).map(|t| &t.value);
+        let kind = match (&token.value, next) {
+            (Token::Define, Some(Token::Window)) => Kind::Window,
+            (Token::Define, Some(Token::Operator)) => Kind::Operator,
+            (Token::Define, Some(Token::Script)) => Kind::Script,
+            (Token::Define, Some(Token::Pipeline)) => Kind::Pipeline,
+            (Token::Define, Some(Token::Connector)) => Kind::Connector,
+            (Token::Define, Some(Token::Flow)) => Kind::Flow,
+            (Token::Create, Some(Token::Stream)) => Kind::Stream,
+            (
+                Token::Create,
+                Some(Token::Operator | Token::Script | Token::Pipeline | Token::Connector),
+            ) => Kind::Instance,
+            (Token::Deploy, Some(Token::Flow)) => Kind::Deployment,
+            (Token::Select, _) => Kind::Select,
+            (
+                Token::Semi,
+                None
+                | Some(
+                    Token::Define
+                    | Token::Create
+                    | Token::Deploy
+                    | Token::Select
+                    | Token::Use
+                    | Token::Connect,
+                ),
+            ) => {
+                close(&mut open, &mut definitions, start);
+                continue;
+            }
+            // the `;` of the last statement in a pipeline or flow, or of the
+            // `end` of one, as opposed to the last one in a script
+            (Token::Semi, Some(Token::End))
+                if tokens[i - 1].value == Token::End
+                    || open.last().map_or(false, |d| {
+                        !matches!(d.kind, Kind::Pipeline | Kind::Flow | Kind::Script)
+                    }) =>
+            {
+                close(&mut open, &mut definitions, start);
+                continue;
+            }
+            _ => continue,
+        };
+        let name = if kind == Kind::Select {
+            Some("select".to_string())
+        } else {
+            tokens
+                .get(i + 2)
+                .and_then(|t| ident(t))
+                .map(|(name, _)| name)
+        };
+        if let Some(name) = name {
+            open.push(Definition::at(&name, kind, start..len));
+        }
+    }
+    while !open.is_empty() {
+        close(&mut open, &mut definitions, len);
+    }
+    definitions
+}
+
+// merges definitions that are found both as statements and in a scope
+fn merge(mut definitions: Vec<Definition>, other: Vec<Definition>) -> Vec<Definition> {
+    for definition in other {
+        if !definitions.iter().any(|d| d.extent == definition.extent) {
+            definitions.push(definition);
+        }
+    }
+    definitions.sort_by_key(|d| d.extent.start);
+    definitions
+}
+
+fn pipeline_definition(pipeline: &PipelineDefinition, aid: arena::Index) -> Definition {
+    Definition::new(&pipeline.id, Kind::Pipeline, pipeline).with_children(query_definitions(
+        &pipeline.stmts,
+        &pipeline.scope.content,
+        aid,
+    ))
+}
+
+fn flow_definition(flow: &FlowDefinition, aid: arena::Index) -> Definition {
+    let definition = Definition::new(&flow.id, Kind::Flow, flow);
+    let mut children = Vec::new();
+    for create in &flow.creates {
+        // definitions local to the flow are only retained by the AST if they
+        // are created
+        let target = match &create.defn {
+            CreateTargetDefinition::Connector(c) if c.aid() == aid => {
+                Some(Definition::new(&c.id, Kind::Connector, c).with_detail(c.builtin_kind.clone()))
+            }
+            CreateTargetDefinition::Pipeline(p) if p.aid() == aid => {
+                Some(pipeline_definition(p, aid))
+            }
+            _ => None,
+        };
+        if let Some(target) = target.filter(|t| {
+            definition.extent.start <= t.extent.start
+                && t.extent.end <= definition.extent.end
+                && !children.iter().any(|c: &Definition| c.extent == t.extent)
+        }) {
+            children.push(target);
+        }
+        children.push(Definition::new(
+            &create.instance_alias,
+            Kind::Instance,
+            create,
+        ));
+    }
+    children.sort_by_key(|d| d.extent.start);
+    definition.with_children(children)
+}
+
+/// A symbol defined in a document, with byte ranges into its text
+#[derive(Clone, Debug)]
+pub(crate) struct Symbol {
+    pub(crate) name: String,
+    pub(crate) kind: Kind,
+    /// range of the whole definition
+    pub(crate) range: Range<usize>,
+    /// range of the defined name
+    pub(crate) selection: Range<usize>,
+    /// range in which the symbol can be referenced
+    scope: Range<usize>,
+    /// the path of the module, for `use`d modules
+    pub(crate) module: Option<Vec<String>>,
+    /// a short description, like the kind of a connector
+    pub(crate) detail: Option<String>,
+}
+
+/// A symbol in the outline of a document, with the symbols nested in it
+#[derive(Clone, Debug)]
+pub(crate) struct Outline {
+    pub(crate) symbol: usize,
+    pub(crate) children: Vec<Outline>,
+}
+
+/// A reference to a symbol
+#[derive(Clone, Debug)]
+pub(crate) struct Reference {
+    pub(crate) range: Range<usize>,
+    pub(crate) symbol: usize,
+    /// whether the symbol is assigned to, like when rebinding a local
+    pub(crate) write: bool,
+}
+
+/// A module path, like `std::string::format`, as a list of segments
+pub(crate) type Path = Vec<(String, Range<usize>)>;
+
+/// The symbols of a document and all the references to them
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SymbolTable {
+    symbols: Vec<Symbol>,
+    references: Vec<Reference>,
+    paths: Vec<Path>,
+}
+
+/// Whether `token` is whitespace, a newline or a comment, which are
+/// irrelevant to the grammar
+pub(crate) fn is_trivia(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Whitespace(_)
+            | Token::NewLine
+            | Token::SingleLineComment(_)
+            | Token::DocComment(_)
+            | Token::ModComment(_)
+    )
+}
+
+// name and range of the name of an identifier token, without the backticks of
+// escaped identifiers
+fn ident(token: &TokenSpan) -> Option<(String, Range<usize>)> {
+    if let Token::Ident(name, escaped) = &token.value {
+        let (start, end) = (token.span.start().absolute(), token.span.end().absolute());
+        let range = if *escaped {
+            start + 1..end - 1
+        } else {
+            start..end
+        };
+        Some((name.to_string(), range))
+    } else {
+        None
+    }
+}
+
+/// Range of the name of the first declaration of `name` in `tokens`, like
+/// `const name`, `fn name` or `define window name`. Found from the tokens alone,
+/// for documents that don't parse.
+pub(crate) fn first_declaration(tokens: &[TokenSpan], name: &str) -> Option<Range<usize>> {
+    let tokens: Vec<&TokenSpan> = tokens.iter().filter(|t| !is_trivia(&t.value)).collect();
+    tokens.iter().enumerate().find_map(|(i, token)| {
+        // `define` and `create` are followed by the kind of what they declare
+        let at = match token.value {
+            Token::Const | Token::Fun => i + 1,
+            Token::Define | Token::Create => i + 2,
+            _ => return None,
+        };
+        tokens
+            .get(at)
+            .and_then(|t| ident(t))
+            .filter(|(n, _)| n == name)
+            .map(|(_, range)| range)
+    })
+}
+
+/// Where a `use` statement for code at `offset` goes: the start of the line
+/// after the last `use` of the script body `offset` is in, or else of the
+/// document, or before the first statement there. Returned along with the
+/// indentation of the statements there.
+pub(crate) fn use_insertion(text: &str, tokens: &[TokenSpan], offset: usize) -> (usize, String) {
+    let tokens: Vec<&TokenSpan> = tokens.iter().filter(|t| !is_trivia(&t.value)).collect();
+    let bodies: Vec<Range<usize>> = tokens
+        .iter()
+        .enumerate()
+        .filter(|(i, t)| {
+            t.value == Token::Script && (*i == 0 || tokens[i - 1].value != Token::Define)
+        })
+        .filter_map(|(i, _)| Some(i + 1..block_end(&tokens, i)?))
+        .collect();
+    let body_of = |i: usize| bodies.iter().find(|body| body.contains(&i));
+    let scope = (0..tokens.len())
+        .find(|i| tokens[*i].span.start().absolute() >= offset)
+        .and_then(body_of);
+    let in_scope = |i: &usize| body_of(*i) == scope;
+    let line_start = |offset: usize| text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let indentation = |start: usize| {
+        text[start..]
+            .chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .collect::<String>()
+    };
+    let last_use = (0..tokens.len())
+        .filter(in_scope)
+        .filter(|i| tokens[*i].value == Token::Use)
+        .last();
+    if let Some(u) = last_use {
+        let semi = (u..tokens.len()).find(|i| tokens[*i].value == Token::Semi);
+        let end = semi.map_or(text.len(), |i| tokens[i].span.end().absolute());
+        let line_end = text[end..].find('\n').map_or(text.len(), |i| end + i + 1);
+        return (
+            line_end,
+            indentation(line_start(tokens[u].span.start().absolute())),
+        );
+    }
+    let first = scope.map_or(0, |body| body.start);
+    match tokens.get(first) {
+        Some(token) if scope.map_or(true, |body| first < body.end) => {
+            let start = line_start(token.span.start().absolute());
+            (start, indentation(start))
+        }
+        _ => (0, String::new()),
+    }
+}
+
+/// The names at `offset` which aren't defined by the document: `event`, `$`
+/// and `args` within `define script` bodies and selects, `state` in the former
+/// and `window` and `group` in selects over windows or with a `group by`.
+/// Functions, and modules outside of them, see none of these. Found from the
+/// tokens alone, for documents that don't parse.
+pub(crate) fn implicit_names(tokens: &[TokenSpan], offset: usize) -> Vec<&'static str> {
+    let tokens: Vec<&TokenSpan> = tokens.iter().filter(|t| !is_trivia(&t.value)).collect();
+    let before = tokens
+        .iter()
+        .take_while(|t| t.span.end().absolute() < offset)
+        .count();
+    // whether the block opened at `i` is still open at `offset`
+    let open = |i: usize| {
+        block_end(&tokens, i).map_or(true, |end| tokens[end].span.start().absolute() >= offset)
+    };
+    // the innermost construct enclosing `offset`
+    let enclosing = (0..before).rev().find_map(|i| match tokens[i].value {
+        Token::Fun if open(i) => Some((Token::Fun, i)),
+        Token::Script if (i == 0 || tokens[i - 1].value != Token::Define) && open(i) => {
+            Some((Token::Script, i))
+        }
+        Token::Select if !tokens[i..before].iter().any(|t| t.value == Token::Semi) => {
+            Some((Token::Select, i))
+        }
+        _ => None,
+    });
+    match enclosing {
+        Some((Token::Script, _)) => vec!["event", "state", "$", "args"],
+        Some((Token::Select, start)) => {
+            let select: Vec<&Token> = tokens[start..]
+                .iter()
+                .map(|t| &t.value)
+                .take_while(|t| **t != Token::Semi)
+                .collect();
+            let mut names = vec!["event", "$", "args"];
+            // `from in[w]`
+            if select
+                .windows(3)
+                .any(|w| matches!(w, [Token::From, Token::Ident(..), Token::LBracket]))
+            {
+                names.push("window");
+            }
+            if select
+                .windows(2)
+                .any(|w| matches!(w, [Token::Group, Token::By]))
+            {
+                names.push("group");
+            }
+            names
+        }
+        _ => vec![],
+    }
+}
+
+/// The call whose arguments `offset` is in, innermost first: the path of the
+/// called function, like `string::format`, and the index of the argument at
+/// `offset`. Found from the tokens alone, for calls still being written.
+pub(crate) fn call_at(tokens: &[TokenSpan], offset: usize) -> Option<(Path, usize)> {
+    let tokens: Vec<&TokenSpan> = tokens
+        .iter()
+        .filter(|t| !is_trivia(&t.value) && t.span.start().absolute() < offset)
+        .collect();
+    // the open brackets, and the number of commas directly within them
+    let mut open: Vec<(usize, usize)> = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        match token.value {
+            Token::LParen
+            | Token::LPatParen
+            | Token::LBracket
+            | Token::LPatBracket
+            | Token::LBrace
+            | Token::LPatBrace
+            | Token::Interpol => open.push((i, 0)),
+            Token::RParen | Token::RBracket | Token::RBrace => {
+                open.pop();
+            }
+            Token::Comma => {
+                if let Some((_, commas)) = open.last_mut() {
+                    *commas += 1;
+                }
+            }
+            _ => (),
+        }
+    }
+    open.iter().rev().find_map(|(i, commas)| {
+        if tokens[*i].value != Token::LParen {
+            return None;
+        }
+        // the path before the parenthesis, read backwards
+        let mut path = Vec::new();
+        let mut j = *i;
+        while let Some(segment) = j.checked_sub(1).and_then(|p| ident(tokens[p])) {
+            path.push(segment);
+            j -= 1;
+            if j >= 1 && tokens[j - 1].value == Token::ColonColon {
+                j -= 1;
+            } else {
+                break;
+            }
+        }
+        path.reverse();
+        // the arguments of function definitions aren't those of a call
+        let definition = j
+            .checked_sub(1)
+            .map_or(false, |p| tokens[p].value == Token::Fun);
+        (!path.is_empty() && !definition).then_some((path, *commas))
+    })
+}
+
+/// The parameters of the function whose name starts at `offset`, with a last
+/// `...` for the rest of the arguments of variadic functions
+pub(crate) fn parameters(tokens: &[TokenSpan], offset: usize) -> Vec<String> {
+    let mut parameters = Vec::new();
+    let mut dots = 0;
+    for token in tokens
+        .iter()
+        .filter(|t| !is_trivia(&t.value))
+        .skip_while(|t| t.span.start().absolute() < offset)
+        .skip(2)
+    {
+        match &token.value {
+            Token::Ident(..) => parameters.extend(ident(token).map(|(name, _)| name)),
+            Token::Dot => {
+                dots += 1;
+                if dots == 3 {
+                    parameters.push("...".to_string());
+                }
+            }
+            Token::Comma => (),
+            _ => break,
+        }
+    }
+    parameters
+}
+
+/// What a `let` or `const` assigns, when it is a single term
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Assigned {
+    /// a literal, like `42`, `-1.5`, `"snot"` or `null`
+    Literal(Range<usize>),
+    /// another name, like `LIMIT`
+    Name(Range<usize>),
+}
+
+/// The value assigned to the name starting at `offset`, if it is a literal or
+/// another name rather than an expression
+pub(crate) fn assigned(tokens: &[TokenSpan], offset: usize) -> Option<Assigned> {
+    let tokens: Vec<&TokenSpan> = tokens
+        .iter()
+        .filter(|t| !is_trivia(&t.value))
+        .skip_while(|t| t.span.start().absolute() < offset)
+        .skip(1)
+        .collect();
+    let values: Vec<&Token> = tokens.iter().map(|t| &t.value).collect();
+    let [Token::Eq, term @ ..] = values.as_slice() else {
+        return None;
+    };
+    let (len, literal) = match term {
+        [Token::BoolLiteral(_) | Token::Nil, ..] => (1, true),
+        [number, ..] if is_number(number) => (1, true),
+        [Token::Sub, number, ..] if is_number(number) => (2, true),
+        // strings without interpolation
+        [Token::DQuote, Token::DQuote, ..] => (2, true),
+        [Token::DQuote, Token::StringLiteral(_), Token::DQuote, ..] => (3, true),
+        [Token::Ident(..), ..] => (1, false),
+        _ => return None,
+    };
+    // anything else following makes for an expression
+    if !matches!(values.get(len + 1), None | Some(Token::Semi | Token::End)) {
+        return None;
+    }
+    let range = tokens[1].span.start().absolute()..tokens[len].span.end().absolute();
+    Some(if literal {
+        Assigned::Literal(range)
+    } else {
+        Assigned::Name(range)
+    })
+}
+
+fn is_number(token: &Token) -> bool {
+    matches!(token, Token::IntLiteral(_) | Token::FloatLiteral(..))
+}
+
+/// The doc comments right before the definition whose name starts at `offset`,
+/// as one line each
+pub(crate) fn doc_comment(tokens: &[TokenSpan], offset: usize) -> Option<String> {
+    let mut lines = Vec::new();
+    let mut keyword = true;
+    for token in tokens
+        .iter()
+        .rev()
+        .skip_while(|t| t.span.start().absolute() >= offset)
+    {
+        match &token.value {
+            Token::Whitespace(_) | Token::NewLine => (),
+            // the keywords the definition starts with, like `intrinsic fn`
+            Token::Fun if keyword => (),
+            Token::Const | Token::Intrinsic if keyword => keyword = false,
+            Token::DocComment(line) => {
+                keyword = false;
+                lines.push(line.trim());
+            }
+            _ => break,
+        }
+    }
+    lines.reverse();
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
+// whether `name` is an identifier that can be written without backticks
+fn is_identifier(name: &str) -> std::result::Result<(), String> {
+    let Ok((aid, text)) = Arena::insert(name) else {
+        return Err(format!("`{name}` is not a valid identifier"));
+    };
+    let tokens: Vec<Token> = Lexer::new(text, aid)
+        .filter_map(|t| Some(t.ok()?.value))
+        .filter(|t| !is_trivia(t))
+        .collect();
+    let result = match tokens.as_slice() {
+        [Token::Ident(_, false)] if !name.trim().is_empty() && name.trim() == name => Ok(()),
+        [_] if name.chars().all(|c| c.is_ascii_alphabetic()) => {
+            Err(format!("`{name}` is a reserved keyword"))
+        }
+        _ => Err(format!("`{name}` is not a valid identifier")),
+    };
+    drop(tokens);
+    unsafe { Arena::delte_index_this_is_really_unsafe_dont_use_it(aid).unwrap() };
+    result
+}
+
+// the index of the `end` closing the block opened at `start`
+fn block_end(tokens: &[&TokenSpan], start: usize) -> Option<usize> {
+    let mut depth = 0_usize;
+    for (i, token) in tokens.iter().enumerate().skip(start) {
+        match token.value {
+            Token::Fun | Token::Match | Token::For | Token::Patch => depth += 1,
+            // the body of a `define script`, as opposed to its kind
+            Token::Script if i == 0 || tokens[i - 1].value != Token::Define => depth += 1,
+            // `merge` is also an operation in patches, without an `end`
+            Token::Merge if !matches!(tokens[i - 1].value, Token::Of | Token::Semi) => {
+                depth += 1;
+            }
+            Token::End => {
+                depth = depth.checked_sub(1)?;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+// the module path starting at `start`, and the index of the token following it
+fn module_path(tokens: &[&TokenSpan], start: usize) -> (Path, usize) {
+    let mut path = Vec::new();
+    let mut i = start;
+    while let Some(segment) = tokens.get(i).and_then(|t| ident(t)) {
+        path.push(segment);
+        i += 1;
+        if tokens.get(i).map(|t| &t.value) == Some(&Token::ColonColon) {
+            i += 1;
+        } else {
+            break;
+        }
+    }
+    (path, i)
+}
+
+impl SymbolTable {
+    pub(crate) fn new(tokens: &[TokenSpan], definitions: Vec<Definition>, len: usize) -> Self {
+        let tokens: Vec<&TokenSpan> = tokens.iter().filter(|t| !is_trivia(&t.value)).collect();
+        let mut table = Self::default();
+        for definition in definitions {
+            table.add_definition(&tokens, definition, &(0..len));
+        }
+        table.scan_bindings(&tokens, len);
+        table.scan_references(&tokens);
+        table
+    }
+
+    fn add_definition(
+        &mut self,
+        tokens: &[&TokenSpan],
+        definition: Definition,
+        scope: &Range<usize>,
+    ) {
+        let Definition {
+            name,
+            kind,
+            extent,
+            detail,
+            children,
+        } = definition;
+        let in_extent: Vec<&&TokenSpan> = tokens
+            .iter()
+            .filter(|t| extent.contains(&t.span.start().absolute()))
+            .collect();
+        // the extent of an AST node can extend up to the start of the token
+        // following it, so we trim it to the last token within
+        let range = extent.start
+            ..in_extent
+                .last()
+                .map_or(extent.end, |t| t.span.end().absolute());
+        let selection = in_extent
+            .iter()
+            .filter_map(|t| ident(t))
+            .find(|(n, _)| n == &name)
+            .map_or(range.start..range.start, |(_, r)| r);
+        let scope = if kind.is_sequential() {
+            selection.start..scope.end
+        } else {
+            scope.clone()
+        };
+        self.symbols.push(Symbol {
+            name,
+            kind,
+            range: range.clone(),
+            selection,
+            scope,
+            module: None,
+            detail,
+        });
+        for child in children {
+            self.add_definition(tokens, child, &range);
+        }
+    }
+
+    // innermost container symbol enclosing `offset`
+    fn container(&self, offset: usize) -> Option<&Symbol> {
+        self.symbols
+            .iter()
+            .filter(|s| s.kind.is_container() && s.range.contains(&offset))
+            .min_by_key(|s| s.range.len())
+    }
+
+    fn is_definition(&self, range: &Range<usize>) -> bool {
+        self.symbols.iter().any(|s| &s.selection == range)
+    }
+
+    fn is_top_level(&self, symbol: &Symbol) -> bool {
+        !self.symbols.iter().any(|o| {
+            o.kind.is_container()
+                && o.range != symbol.range
+                && o.range.start <= symbol.range.start
+                && symbol.range.end <= o.range.end
+        })
+    }
+
+    // whether `symbol` can be referenced at `offset`. pipelines, scripts and
+    // functions only see what is defined within them or at the top level.
+    fn is_visible(&self, symbol: &Symbol, offset: usize) -> bool {
+        symbol.scope.contains(&offset)
+            && self
+                .symbols
+                .iter()
+                .filter(|c| {
+                    matches!(c.kind, Kind::Pipeline | Kind::Script | Kind::Function)
+                        && c.range.contains(&offset)
+                })
+                .min_by_key(|c| c.range.len())
+                .map_or(true, |c| {
+                    c.range.contains(&symbol.selection.start) || self.is_top_level(symbol)
+                })
+    }
+
+    // the symbol `name` refers to at `offset`
+    fn resolve(&self, name: &str, offset: usize, module: bool) -> Option<usize> {
+        self.symbols
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| {
+                s.name == name && (s.kind == Kind::Module) == module && self.is_visible(s, offset)
+            })
+            .min_by_key(|(i, s)| (s.scope.len(), usize::MAX - i))
+            .map(|(i, _)| i)
+    }
+
+    // defines a symbol found in the token stream, unless it is the rebinding of
+    // a local that is already in scope
+    fn bind(&mut self, kind: Kind, name: String, range: Range<usize>, len: usize) {
+        if self.is_definition(&range) {
+            return;
+        }
+        let container = self.container(range.start).map(|c| c.range.clone());
+        if let Some(symbol) = self.resolve(&name, range.start, false).filter(|i| {
+            let s = &self.symbols[*i];
+            kind == Kind::Local
+                && s.kind.is_sequential()
+                && container
+                    .as_ref()
+                    .map_or(true, |c| c.contains(&s.selection.start))
+        }) {
+            self.references.push(Reference {
+                range,
+                symbol,
+                write: true,
+            });
+        } else {
+            let end = container.map_or(len, |c| c.end);
+            self.symbols.push(Symbol {
+                name,
+                kind,
+                range: range.clone(),
+                scope: range.start..end,
+                selection: range,
+                module: None,
+                detail: None,
+            });
+        }
+    }
+
+    // binds the comma separated identifiers starting at `start`, as in
+    // `fn f(a, b)` or `case (k, v)`
+    fn bind_list(&mut self, tokens: &[&TokenSpan], start: usize, kind: Kind, len: usize) {
+        for t in tokens.iter().skip(start) {
+            match &t.value {
+                Token::Ident(..) => {
+                    if let Some((name, range)) = ident(t) {
+                        self.bind(kind, name, range, len);
+                    }
+                }
+                Token::Comma => (),
+                _ => break,
+            }
+        }
+    }
+
+    fn scan_bindings(&mut self, tokens: &[&TokenSpan], len: usize) {
+        let mut i = 0;
+        while i < tokens.len() {
+            let next = tokens.get(i + 1).and_then(|t| ident(t));
+            match &tokens[i].value {
+                Token::Let => {
+                    if let Some((name, range)) = next {
+                        self.bind(Kind::Local, name, range, len);
+                    }
+                }
+                // `for` cases bind keys and values: `case (k, v) =>`
+                Token::Case if tokens.get(i + 1).map(|t| &t.value) == Some(&Token::LParen) => {
+                    self.bind_list(tokens, i + 2, Kind::Local, len);
+                }
+                // match cases can bind the matched value: `case x = %{...} =>`
+                Token::Case => {
+                    if let (Some((name, range)), Some(Token::Eq)) =
+                        (next, tokens.get(i + 2).map(|t| &t.value))
+                    {
+                        self.bind(Kind::Local, name, range, len);
+                    }
+                }
+                // consts and functions of scripts aren't retained by the AST
+                Token::Const => {
+                    if let Some((name, range)) = next {
+                        self.bind(Kind::Const, name, range, len);
+                    }
+                }
+                Token::Fun => {
+                    if let Some((name, range)) = next {
+                        let defined = self.symbols.len();
+                        self.bind(Kind::Function, name, range, len);
+                        if let (Some(symbol), Some(end)) =
+                            (self.symbols.get_mut(defined), block_end(tokens, i))
+                        {
+                            symbol.range = tokens[i].span.start().absolute()
+                                ..tokens[end].span.end().absolute();
+                        }
+                    }
+                    if let Some(Token::LParen) = tokens.get(i + 2).map(|t| &t.value) {
+                        self.bind_list(tokens, i + 3, Kind::Argument, len);
+                    }
+                }
+                Token::Use => {
+                    let (path, end) = module_path(tokens, i + 1);
+                    let alias = match tokens.get(end).map(|t| &t.value) {
+                        Some(Token::As) => tokens.get(end + 1).and_then(|t| ident(t)),
+                        _ => path.last().cloned(),
+                    };
+                    if let Some((name, selection)) = alias {
+                        let scope_end =
+                            self.container(selection.start).map_or(len, |c| c.range.end);
+                        self.symbols.push(Symbol {
+                            name,
+                            kind: Kind::Module,
+                            range: tokens[i].span.start().absolute()..selection.end,
+                            scope: selection.start..scope_end,
+                            selection,
+                            module: Some(path.iter().map(|(s, _)| s.clone()).collect()),
+                            detail: None,
+                        });
+                    }
+                    if path.len() > 1 {
+                        self.paths.push(path);
+                    }
+                    i = end;
+                    continue;
+                }
+                Token::Ident(..) => {
+                    let (path, end) = module_path(tokens, i);
+                    if path.len() > 1 {
+                        self.paths.push(path);
+                        i = end;
+                        continue;
+                    }
+                }
+                _ => (),
+            }
+            i += 1;
+        }
+    }
+
+    fn scan_references(&mut self, tokens: &[&TokenSpan]) {
+        for (i, token) in tokens.iter().enumerate() {
+            let Some((name, range)) = ident(token) else {
+                continue;
+            };
+            let prev = i
+                .checked_sub(1)
+                .and_then(|p| tokens.get(p))
+                .map(|t| &t.value);
+            let next = tokens.get(i + 1).map(|t| &t.value);
+            if self.is_definition(&range) {
+                // `create operator name;` creates an instance from the
+                // definition with the same name
+                let instance = self
+                    .symbols
+                    .iter()
+                    .any(|s| s.selection == range && s.kind == Kind::Instance);
+                if instance && next != Some(&Token::From) {
+                    if let Some(symbol) = self.resolve_target(&name, range.start) {
+                        self.references.push(Reference {
+                            range,
+                            symbol,
+                            write: false,
+                        });
+                    }
+                }
+                continue;
+            }
+            match (prev, next) {
+                // field accesses, metadata and members of modules, and rebound
+                // locals which were already resolved
+                (Some(Token::Dot | Token::Dollar | Token::ColonColon | Token::Let), _) => (),
+                // builtin kinds of definitions: `define window w from tumbling`
+                (Some(Token::From), _) if i >= 4 && tokens[i - 4].value == Token::Define => {}
+                // module paths
+                (_, Some(Token::ColonColon)) => {
+                    if let Some(symbol) = self.resolve(&name, range.start, true) {
+                        self.references.push(Reference {
+                            range,
+                            symbol,
+                            write: false,
+                        });
+                    }
+                }
+                // configuration in `with` and `args` blocks
+                (_, Some(Token::Eq)) => (),
+                _ => {
+                    if let Some(symbol) = self.resolve(&name, range.start, false) {
+                        self.references.push(Reference {
+                            range,
+                            symbol,
+                            write: false,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    // the definition an instance named `name` is created from
+    fn resolve_target(&self, name: &str, offset: usize) -> Option<usize> {
+        self.symbols
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| {
+                s.name == name
+                    && self.is_visible(s, offset)
+                    && matches!(
+                        s.kind,
+                        Kind::Operator | Kind::Script | Kind::Pipeline | Kind::Connector
+                    )
+            })
+            .min_by_key(|(_, s)| s.scope.len())
+            .map(|(i, _)| i)
+    }
+
+    pub(crate) fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    pub(crate) fn references(&self) -> &[Reference] {
+        &self.references
+    }
+
+    /// The variables, consts and functions that can be referenced at
+    /// `offset`, without the ones shadowed there
+    pub(crate) fn visible(&self, offset: usize) -> Vec<usize> {
+        self.symbols
+            .iter()
+            .enumerate()
+            .filter(|(i, s)| {
+                matches!(
+                    s.kind,
+                    Kind::Const | Kind::Function | Kind::Argument | Kind::Local
+                ) && !(s.selection.start <= offset && offset <= s.selection.end)
+                    && self.resolve(&s.name, offset, false) == Some(*i)
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// The names of the arguments of a function
+    pub(crate) fn arguments(&self, function: usize) -> Vec<&str> {
+        let range = &self.symbols[function].range;
+        self.symbols
+            .iter()
+            .filter(|s| s.kind == Kind::Argument && range.contains(&s.selection.start))
+            .map(|s| s.name.as_str())
+            .collect()
+    }
+
+    /// The symbol defined or referenced at `offset`
+    pub(crate) fn symbol_at(&self, offset: usize) -> Option<usize> {
+        let at = |r: &Range<usize>| r.start <= offset && offset <= r.end;
+        self.symbols
+            .iter()
+            .position(|s| at(&s.selection) && !s.selection.is_empty())
+            .or_else(|| {
+                self.references
+                    .iter()
+                    .find(|r| at(&r.range))
+                    .map(|r| r.symbol)
+            })
+    }
+
+    /// The ranges of the definition of a symbol and of all references to it
+    pub(crate) fn occurrences(&self, symbol: usize) -> impl Iterator<Item = &Range<usize>> {
+        std::iter::once(&self.symbols[symbol].selection).chain(
+            self.references
+                .iter()
+                .filter(move |r| r.symbol == symbol)
+                .map(|r| &r.range),
+        )
+    }
+
+    /// The occurrences of `symbol`, along with whether they write to it: its
+    /// definition and rebindings do, all other references only read it
+    pub(crate) fn accesses(&self, symbol: usize) -> impl Iterator<Item = (&Range<usize>, bool)> {
+        let selection = &self.symbols[symbol].selection;
+        std::iter::once((selection, true))
+            .filter(|(range, _)| !range.is_empty())
+            .chain(
+                self.references
+                    .iter()
+                    .filter(move |r| r.symbol == symbol)
+                    .map(|r| (&r.range, r.write)),
+            )
+    }
+
+    /// The segments of a module path, with a `use`d alias at its start
+    /// replaced by the module it refers to
+    pub(crate) fn expand(&self, path: &Path) -> Vec<String> {
+        let alias = path.first().and_then(|(_, r)| {
+            self.symbol_at(r.start)
+                .and_then(|i| self.symbols[i].module.clone())
+        });
+        let skip = usize::from(alias.is_some());
+        alias
+            .into_iter()
+            .flatten()
+            .chain(path.iter().skip(skip).map(|(name, _)| name.clone()))
+            .collect()
+    }
+
+    /// The module a path ending in `::` right before `offset` refers to, like
+    /// `a::b` for `b::` after `use a::b;`. Paths have to start with a `use`d
+    /// module.
+    pub(crate) fn module_before(&self, tokens: &[TokenSpan], offset: usize) -> Option<Vec<String>> {
+        let tokens: Vec<&TokenSpan> = tokens
+            .iter()
+            .filter(|t| !is_trivia(&t.value) && t.span.end().absolute() <= offset)
+            .collect();
+        let mut path = Vec::new();
+        let mut i = tokens.len();
+        while i >= 2 && tokens[i - 1].value == Token::ColonColon {
+            let Some(segment) = ident(tokens[i - 2]) else {
+                break;
+            };
+            path.push(segment);
+            i -= 2;
+        }
+        path.reverse();
+        let (_, first) = path.first()?;
+        self.symbol_at(first.start)
+            .and_then(|i| self.symbols[i].module.as_ref())?;
+        Some(self.expand(&path))
+    }
+
+    /// The module path at `offset`, with aliases expanded, and the index of
+    /// the segment at `offset` in it
+    pub(crate) fn path_at(&self, offset: usize) -> Option<(Vec<String>, usize)> {
+        self.paths.iter().find_map(|path| {
+            let segment = path
+                .iter()
+                .position(|(_, r)| r.start <= offset && offset <= r.end)?;
+            let expanded = self.expand(path);
+            let segment = segment + expanded.len() - path.len();
+            Some((expanded, segment))
+        })
+    }
+
+    /// The ranges of the references to `member` of `module` through module
+    /// paths
+    pub(crate) fn member_references(&self, module: &[String], member: &str) -> Vec<Range<usize>> {
+        self.paths
+            .iter()
+            .filter_map(|path| {
+                let expanded = self.expand(path);
+                let (last, prefix) = expanded.split_last()?;
+                (last == member && prefix == module).then(|| path.last().map(|(_, r)| r.clone()))?
+            })
+            .collect()
+    }
+
+    /// The top-level symbol named `name`
+    pub(crate) fn top_level(&self, name: &str) -> Option<usize> {
+        (0..self.symbols.len()).find(|i| self.symbols[*i].name == name && self.is_exported(*i))
+    }
+
+    /// Whether a symbol is defined at the top level of the document
+    pub(crate) fn is_exported(&self, symbol: usize) -> bool {
+        let symbol = &self.symbols[symbol];
+        symbol.kind.is_container() && self.is_top_level(symbol)
+    }
+
+    /// The range of the identifier at `offset`, if it names a symbol or a
+    /// member of a module
+    pub(crate) fn name_at(&self, offset: usize) -> Option<Range<usize>> {
+        let at = |r: &&Range<usize>| r.start <= offset && offset <= r.end;
+        self.symbols
+            .iter()
+            .map(|s| &s.selection)
+            .filter(|r| !r.is_empty())
+            .chain(self.references.iter().map(|r| &r.range))
+            .chain(self.paths.iter().filter_map(|p| p.last().map(|(_, r)| r)))
+            .find(at)
+            .cloned()
+    }
+
+    /// Checks whether `symbol` can be renamed at all
+    pub(crate) fn check_renamable(&self, symbol: usize) -> std::result::Result<(), String> {
+        let s = &self.symbols[symbol];
+        match s.kind {
+            Kind::Select => Err("selects can't be renamed".to_string()),
+            // renaming a module which isn't aliased would require adding an alias
+            Kind::Module if s.module.as_ref().and_then(|m| m.last()) == Some(&s.name) => {
+                Err(format!("`{}` is the name of a module", s.name))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks whether `symbol` can be renamed to `name`
+    pub(crate) fn check_rename(
+        &self,
+        symbol: usize,
+        name: &str,
+    ) -> std::result::Result<(), String> {
+        self.check_renamable(symbol)?;
+        is_identifier(name)?;
+        let s = &self.symbols[symbol];
+        if s.kind == Kind::Const && name.chars().any(char::is_lowercase) {
+            return Err(format!(
+                "const's are canonically written in UPPER_CASE, `{name}` is not"
+            ));
+        }
+        let module = s.kind == Kind::Module;
+        let shadowed = self.occurrences(symbol).find_map(|r| {
+            self.resolve(name, r.start, module)
+                .filter(|other| *other != symbol)
+        });
+        let clashing = self.symbols.iter().enumerate().find(|(i, other)| {
+            *i != symbol
+                && other.name == name
+                && (other.kind == Kind::Module) == module
+                && other.scope.contains(&s.selection.start)
+        });
+        if let Some(other) = shadowed.or(clashing.map(|(i, _)| i)) {
+            let other = &self.symbols[other];
+            return Err(format!(
+                "`{name}` would clash with the {} `{name}`",
+                other.kind.description()
+            ));
+        }
+        Ok(())
+    }
+
+    /// The outline of the document: its definitions, nested in the
+    /// definitions they are made in, and its top-level `let`s
+    pub(crate) fn outline(&self) -> Vec<Outline> {
+        let mut symbols: Vec<usize> = (0..self.symbols.len())
+            .filter(|i| {
+                let s = &self.symbols[*i];
+                s.kind.is_container() || (s.kind == Kind::Local && self.is_top_level(s))
+            })
+            .collect();
+        symbols.sort_by_key(|i| {
+            let range = &self.symbols[*i].range;
+            (range.start, usize::MAX - range.end)
+        });
+        self.nest(&symbols)
+    }
+
+    fn nest(&self, symbols: &[usize]) -> Vec<Outline> {
+        let mut outline = Vec::new();
+        let mut i = 0;
+        while let Some(symbol) = symbols.get(i) {
+            let range = &self.symbols[*symbol].range;
+            let end = symbols[i + 1..]
+                .iter()
+                .position(|other| range.end < self.symbols[*other].range.end)
+                .map_or(symbols.len(), |p| i + 1 + p);
+            outline.push(Outline {
+                symbol: *symbol,
+                children: self.nest(&symbols[i + 1..end]),
+            });
+            i = end;
+        }
+        outline
+    }
+}
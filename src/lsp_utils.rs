@@ -13,8 +13,10 @@
 // limitations under the License.
 
 use crate::language;
-use tower_lsp::lsp_types::{DiagnosticSeverity, Position};
-use tremor_script::lexer::{Spanned, Token};
+use tower_lsp::lsp_types::{
+    DiagnosticSeverity, DiagnosticTag, Position, Range, SymbolKind, TextDocumentContentChangeEvent,
+};
+use tremor_script::lexer::Token;
 
 use crate::backend::file_dbg;
 
@@ -24,6 +26,58 @@ pub(crate) fn to_lsp_position(location: &language::Location) -> Position {
     Position::new((location.line() - 1) as u32, (location.column() - 1) as u32)
 }
 
+/// Converts a position in language server protocol terms (zero-based line and
+/// UTF-16 code unit column) into a byte offset into `text`. Positions past the
+/// end of a line or of the text are clamped to it.
+pub(crate) fn to_offset(text: &str, position: Position) -> usize {
+    let mut offset = 0;
+    for (i, line) in text.split_inclusive('\n').enumerate() {
+        if i == position.line as usize {
+            let content = line.trim_end_matches(['\n', '\r']);
+            let mut column = 0;
+            for (byte, c) in content.char_indices() {
+                if column >= position.character as usize {
+                    return offset + byte;
+                }
+                column += c.len_utf16();
+            }
+            return offset + content.len();
+        }
+        offset += line.len();
+    }
+    text.len()
+}
+
+/// Converts a byte offset into `text` into a position in language server
+/// protocol terms, the inverse of [`to_offset`]
+#[allow(clippy::cast_possible_truncation)]
+pub(crate) fn to_position(text: &str, offset: usize) -> Position {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position::new(
+        before.matches('\n').count() as u32,
+        before[line_start..].encode_utf16().count() as u32,
+    )
+}
+
+/// Converts a byte range into `text` into a range in language server protocol
+/// terms
+pub(crate) fn to_lsp_range(text: &str, range: &std::ops::Range<usize>) -> Range {
+    Range::new(to_position(text, range.start), to_position(text, range.end))
+}
+
+/// Applies an edit sent by the client to `text`. Edits without a range replace
+/// the whole document.
+pub(crate) fn apply_change(text: &mut String, change: &TextDocumentContentChangeEvent) {
+    if let Some(range) = change.range {
+        let start = to_offset(text, range.start);
+        let end = to_offset(text, range.end).max(start);
+        text.replace_range(start..end, &change.text);
+    } else {
+        text.clone_from(&change.text);
+    }
+}
+
 pub(crate) fn to_lsp_severity(error_level: language::ErrorLevel) -> DiagnosticSeverity {
     match error_level {
         language::ErrorLevel::Error => DiagnosticSeverity::ERROR,
@@ -32,56 +86,155 @@ pub(crate) fn to_lsp_severity(error_level: language::ErrorLevel) -> DiagnosticSe
     }
 }
 
-pub(crate) fn get_token(tokens: &[language::TokenSpan], position: Position) -> Option<String> {
-    let line = position.line as usize;
-    let column = position.character as usize;
-
-    let mut token = None;
-    for (i, t) in tokens.iter().enumerate() {
-        if t.span.end().line() == line && t.span.end().column() > column {
-            file_dbg("get_token_t_value", &t.value.to_string());
-
-            token = match t.value {
-                language::Token::Ident(_, _) => {
-                    if let Some(
-                        [Spanned { value: v1, .. }, Spanned {
-                            value: Token::ColonColon,
-                            ..
-                        }, Spanned { value: v2, .. }],
-                    ) = tokens.get(i - 2..i)
-                    {
-                        Some(format!("{v1}::{v2}"))
-                    } else if let Some(
-                        [Spanned { value: v1, .. }, Spanned {
-                            value: Token::ColonColon,
-                            ..
-                        }, Spanned { value: v2, .. }],
-                    ) = tokens.get(i..i + 3)
-                    {
-                        Some(format!("{v1}::{v2}"))
-                    } else {
-                        None
-                    }
-                }
-                language::Token::ColonColon => {
-                    if let Some(
-                        [Spanned { value: v1, .. }, Spanned {
-                            value: Token::ColonColon,
-                            ..
-                        }, Spanned { value: v2, .. }],
-                    ) = tokens.get(i - 2..i)
-                    {
-                        Some(format!("{v1}::{v2}"))
-                    } else {
-                        None
-                    }
-                }
-                _ => None,
-            };
+/// Tags for warnings about code that is unnecessary or deprecated, which tremor
+/// only tells apart by their messages. Experimental syntax that "may be
+/// deprecated" still works, so only what "is deprecated" is tagged as such.
+pub(crate) fn to_lsp_tags(problem: &language::Problem) -> Option<Vec<DiagnosticTag>> {
+    if !matches!(problem.error.level(), language::ErrorLevel::Warning(_)) {
+        return None;
+    }
+    let message = problem.error.callout();
+    if message.contains(" is deprecated") {
+        Some(vec![DiagnosticTag::DEPRECATED])
+    } else if ["unreachable", "redundant", "can never be true"]
+        .iter()
+        .any(|m| message.contains(m))
+    {
+        Some(vec![DiagnosticTag::UNNECESSARY])
+    } else {
+        None
+    }
+}
+
+pub(crate) fn to_symbol_kind(kind: language::Kind) -> SymbolKind {
+    match kind {
+        language::Kind::Module => SymbolKind::MODULE,
+        language::Kind::Const => SymbolKind::CONSTANT,
+        language::Kind::Function => SymbolKind::FUNCTION,
+        language::Kind::Argument | language::Kind::Local => SymbolKind::VARIABLE,
+        language::Kind::Window => SymbolKind::STRUCT,
+        language::Kind::Operator => SymbolKind::OPERATOR,
+        language::Kind::Script => SymbolKind::METHOD,
+        language::Kind::Pipeline => SymbolKind::CLASS,
+        language::Kind::Connector => SymbolKind::INTERFACE,
+        language::Kind::Flow => SymbolKind::NAMESPACE,
+        language::Kind::Stream | language::Kind::Select => SymbolKind::EVENT,
+        language::Kind::Instance => SymbolKind::OBJECT,
+        language::Kind::Deployment => SymbolKind::PACKAGE,
+    }
+}
 
-            break;
+/// The index of the token whose span contains the byte `offset`. Tokens are
+/// sorted and don't overlap, so they are binary searched.
+pub(crate) fn token_index(tokens: &[language::TokenSpan], offset: usize) -> Option<usize> {
+    let i = tokens.partition_point(|t| t.span.end().absolute() <= offset);
+    tokens
+        .get(i)
+        .filter(|t| t.span.start().absolute() <= offset)
+        .map(|_| i)
+}
+
+/// The token at `position`, spanning the whole module path it is part of
+/// (eg: `a::b::c`), with its byte range into `text`. Whitespace, newlines and
+/// comments are no tokens in this sense.
+pub(crate) fn get_token(
+    text: &str,
+    tokens: &[language::TokenSpan],
+    position: Position,
+) -> Option<(String, std::ops::Range<usize>)> {
+    let i = token_index(tokens, to_offset(text, position))?;
+    if language::is_trivia(&tokens[i].value) {
+        return None;
+    }
+    file_dbg("get_token_t_value", &tokens[i].value.to_string());
+    let in_path = |t: &language::TokenSpan| matches!(t.value, Token::Ident(..) | Token::ColonColon);
+    let (mut first, mut last) = (i, i);
+    if in_path(&tokens[i]) {
+        while first > 0 && in_path(&tokens[first - 1]) {
+            first -= 1;
+        }
+        while last + 1 < tokens.len() && in_path(&tokens[last + 1]) {
+            last += 1;
         }
     }
+    let range = tokens[first].span.start().absolute()..tokens[last].span.end().absolute();
+    Some((text.get(range.clone())?.to_string(), range))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tremor_script::arena::Arena;
+    use tremor_script::lexer::Lexer;
 
-    token
+    fn change(start: (u32, u32), end: (u32, u32), text: &str) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range: Some(Range::new(
+                Position::new(start.0, start.1),
+                Position::new(end.0, end.1),
+            )),
+            range_length: None,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn incremental_changes() {
+        let mut text = "let a = 1;\nlet b = \"🦀\" + a;\n".to_string();
+        // the crab is two UTF-16 code units wide
+        apply_change(&mut text, &change((1, 8), (1, 12), "\"x\""));
+        assert_eq!("let a = 1;\nlet b = \"x\" + a;\n", text);
+        apply_change(&mut text, &change((0, 4), (0, 5), "c"));
+        apply_change(&mut text, &change((1, 14), (1, 15), "c"));
+        assert_eq!("let c = 1;\nlet b = \"x\" + c;\n", text);
+        apply_change(&mut text, &change((2, 0), (2, 0), "emit b"));
+        assert_eq!("let c = 1;\nlet b = \"x\" + c;\nemit b", text);
+        apply_change(
+            &mut text,
+            &TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: "drop".to_string(),
+            },
+        );
+        assert_eq!("drop", text);
+    }
+
+    #[test]
+    fn tokens_at_positions() {
+        let text = "use std::string;\nlet a = std::string::format(h);\nlet h = \"\"\"\n  one\n  two\n\"\"\";\n# done\n";
+        let (aid, input) = Arena::insert(text).unwrap();
+        let tokens: Vec<language::TokenSpan> =
+            Lexer::new(input, aid).collect::<Result<_, _>>().unwrap();
+        let token = |line, character| get_token(text, &tokens, Position::new(line, character));
+
+        // the whole module path, from any of its segments
+        let path = Some(("std::string::format".to_string(), 25..44));
+        assert_eq!(path, token(1, 8));
+        assert_eq!(path, token(1, 17));
+        assert_eq!(path, token(1, 26));
+        assert_eq!(Some(("use".to_string(), 0..3)), token(0, 0));
+        assert_eq!(Some(("(".to_string(), 44..45)), token(1, 27));
+        // heredocs are a token per line
+        assert_eq!(Some(("  one".to_string(), 61..66)), token(3, 3));
+        assert_eq!(Some(("  two".to_string(), 67..72)), token(4, 0));
+        // whitespace and comments
+        assert_eq!(None, token(1, 3));
+        assert_eq!(None, token(6, 2));
+        assert_eq!(None, token(9, 0));
+
+        drop(tokens);
+        unsafe { Arena::delte_index_this_is_really_unsafe_dont_use_it(aid).unwrap() };
+    }
+
+    #[test]
+    fn offsets_and_positions() {
+        let text = "let a = \"🦀\";\nemit a";
+        let offset = text.find("emit").unwrap();
+        assert_eq!(Position::new(1, 0), to_position(text, offset));
+        assert_eq!(Position::new(0, 13), to_position(text, offset - 1));
+        for offset in [0, 9, offset - 1, offset, text.len()] {
+            assert_eq!(offset, to_offset(text, to_position(text, offset)));
+        }
+    }
 }
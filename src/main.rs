@@ -16,15 +16,21 @@
 #![deny(clippy::all, clippy::pedantic)]
 
 mod backend;
+mod formatter;
 mod language;
 mod lsp_utils;
+mod quick_fix;
+mod semantic_tokens;
+mod snippets;
+mod workspace;
 
 use backend::Backend;
 use clap::{
     builder::{OsStr, PossibleValuesParser, ValueParser},
     Arg, ArgAction, Command,
 };
-use tower_lsp::{LspService, Server};
+use tower_lsp::Server;
+use tremor_script::path::ModulePath;
 
 #[async_std::main]
 async fn main() {
@@ -36,7 +42,7 @@ async fn main() {
         .author(env!("CARGO_PKG_AUTHORS"))
         .arg(
             Arg::new("language")
-                .help("Tremor language to support")
+                .help("Tremor language to assume for documents of unknown language")
                 .short('l')
                 .long("language")
                 .action(ArgAction::Set)
@@ -45,7 +51,7 @@ async fn main() {
         )
         .arg(
             Arg::new("path")
-                .help("TREMOR_PATH to set")
+                .help("Folders to load modules from, in addition to the TREMOR_PATH")
                 .short('p')
                 .long("path")
                 .action(ArgAction::Set)
@@ -60,20 +66,15 @@ async fn main() {
 
     let path: &String = matches.get_one("path").expect("a default value was set");
 
-    if !path.is_empty() {
-        std::env::set_var(
-            "TREMOR_PATH",
-            match std::env::var("TREMOR_PATH") {
-                // append to existing path if it's already set
-                Ok(p) => format!("{p}:{path}"),
-                Err(_) => path.to_string(),
-            },
-        );
+    // appended to the `TREMOR_PATH`, if it is set
+    let mut module_path = ModulePath::load();
+    for mount in path.split(':').filter(|mount| !mount.is_empty()) {
+        module_path.add(&mount);
     }
 
-    if let Some(language) = language::lookup(language_name) {
+    if let Some(language) = language::canonical_name(language_name) {
         let (stdin, stdout) = (async_std::io::stdin(), async_std::io::stdout());
-        let (service, socket) = LspService::new(|client| Backend::new(client, language));
+        let (service, socket) = Backend::service(language, module_path);
         Server::new(stdin, stdout, socket).serve(service).await;
     } else {
         eprintln!("Error: unknown tremor language {language_name}");
@@ -0,0 +1,157 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Fixes for the problems tremor reports. The errors only carry their message
+// (and a hint, for some), so the fixes are derived from those along with the
+// tokens of the document.
+
+use crate::language::{is_trivia, Problem, TokenSpan};
+use crate::lsp_utils;
+use tower_lsp::lsp_types::TextEdit;
+
+// how far off a name can be from the one it is meant to be
+const MAX_DISTANCE: usize = 2;
+
+/// Module path and name of the function an undefined function error is about
+pub(crate) fn undefined_function(problem: &Problem) -> Option<(&str, &str)> {
+    if problem.code != Problem::MISSING_FUNCTION {
+        return None;
+    }
+    let path = problem
+        .error
+        .callout()
+        .strip_prefix("Call to undefined function ")?;
+    path.rsplit_once("::")
+}
+
+/// Whether the problem is the warning about consts not written in `UPPER_CASE`
+pub(crate) fn is_lower_case_const(problem: &Problem) -> bool {
+    problem.code == Problem::CONSISTENCY && problem.error.callout().contains("UPPER_CASE")
+}
+
+/// Levenshtein distance between `a` and `b`
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                diagonal
+            } else {
+                1 + diagonal.min(above).min(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+/// The candidate closest to `name`, if any is close enough to be a misspelling
+pub(crate) fn suggestion<'a>(name: &str, candidates: &'a [String]) -> Option<&'a str> {
+    candidates
+        .iter()
+        .map(|c| (distance(name, c), c))
+        .filter(|(d, _)| *d <= MAX_DISTANCE)
+        .min()
+        .map(|(_, c)| c.as_str())
+}
+
+/// `name` in `UPPER_CASE`, splitting camelCase words with `_`
+pub(crate) fn upper_case(name: &str) -> String {
+    let mut upper = String::with_capacity(name.len());
+    let mut previous: Option<char> = None;
+    for c in name.chars() {
+        if c.is_uppercase() && previous.map_or(false, char::is_lowercase) {
+            upper.push('_');
+        }
+        upper.extend(c.to_uppercase());
+        previous = Some(c);
+    }
+    upper
+}
+
+/// Edit replacing the misspelled function `name` in `module` by `replacement`,
+/// within the call the problem is reported for
+pub(crate) fn rename_function(
+    text: &str,
+    problem: &Problem,
+    module: &str,
+    name: &str,
+    replacement: &str,
+) -> Option<TextEdit> {
+    let (start, end) = (
+        problem.error.start().absolute(),
+        problem.error.end().absolute(),
+    );
+    let path = format!("{module}::{name}");
+    let at = start + text.get(start..end)?.find(&path)? + module.len() + 2;
+    Some(TextEdit::new(
+        lsp_utils::to_lsp_range(text, &(at..at + name.len())),
+        replacement.to_string(),
+    ))
+}
+
+/// The missing `;` or `end` a syntax error asks for, as titled edits. Both
+/// are added right after the last token before the error.
+pub(crate) fn terminators(
+    text: &str,
+    tokens: &[TokenSpan],
+    problem: &Problem,
+) -> Vec<(String, TextEdit)> {
+    let message = problem.error.callout();
+    if problem.code != Problem::UNRECOGNIZED_TOKEN || !message.contains("expected") {
+        return vec![];
+    }
+    let start = problem.error.start();
+    let Some(previous) = tokens
+        .iter()
+        .filter(|t| !is_trivia(&t.value))
+        .take_while(|t| t.span.end().absolute() <= start.absolute())
+        .last()
+    else {
+        return vec![];
+    };
+    let at = previous.span.end().absolute();
+    let range = lsp_utils::to_lsp_range(text, &(at..at));
+    // the token at the start of a line is a new statement, which the one
+    // before has to be terminated for
+    let new_statement = start.column() == 1;
+    let mut fixes = Vec::new();
+    if message.contains("`end`") {
+        let line_start = text[..at].rfind('\n').map_or(0, |i| i + 1);
+        let indentation: String = if new_statement {
+            String::new()
+        } else {
+            text[line_start..]
+                .chars()
+                .take_while(|c| *c == ' ' || *c == '\t')
+                .collect()
+        };
+        let terminator = if new_statement { "end;" } else { "end" };
+        fixes.push((
+            format!("Add missing `{terminator}`"),
+            TextEdit::new(range, format!("\n{indentation}{terminator}")),
+        ));
+    }
+    if message.contains("`;`") {
+        fixes.push((
+            "Add missing `;`".to_string(),
+            TextEdit::new(range, ";".into()),
+        ));
+    }
+    fixes
+}
@@ -0,0 +1,387 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::language::{is_trivia, Kind, SymbolTable, Token, TokenSpan};
+use halfbrown::HashMap;
+use std::ops::Range;
+use tower_lsp::lsp_types::{
+    SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokensEdit,
+    SemanticTokensLegend,
+};
+
+/// Classes of tokens we highlight, in the order of the legend
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Class {
+    Keyword,
+    Operator,
+    String,
+    Number,
+    Regexp,
+    Comment,
+    Namespace,
+    Type,
+    Function,
+    Parameter,
+    Variable,
+    Property,
+}
+
+const TOKEN_TYPES: &[SemanticTokenType] = &[
+    SemanticTokenType::KEYWORD,
+    SemanticTokenType::OPERATOR,
+    SemanticTokenType::STRING,
+    SemanticTokenType::NUMBER,
+    SemanticTokenType::REGEXP,
+    SemanticTokenType::COMMENT,
+    SemanticTokenType::NAMESPACE,
+    SemanticTokenType::TYPE,
+    SemanticTokenType::FUNCTION,
+    SemanticTokenType::PARAMETER,
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::PROPERTY,
+];
+
+// bits of the modifiers, in the order of the legend
+const DECLARATION: u32 = 1;
+const READONLY: u32 = 1 << 1;
+const DOCUMENTATION: u32 = 1 << 2;
+const DEFAULT_LIBRARY: u32 = 1 << 3;
+
+const TOKEN_MODIFIERS: &[SemanticTokenModifier] = &[
+    SemanticTokenModifier::DECLARATION,
+    SemanticTokenModifier::READONLY,
+    SemanticTokenModifier::DOCUMENTATION,
+    SemanticTokenModifier::DEFAULT_LIBRARY,
+];
+
+/// The token types and modifiers the server uses
+pub(crate) fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TOKEN_TYPES.to_vec(),
+        token_modifiers: TOKEN_MODIFIERS.to_vec(),
+    }
+}
+
+/// A highlighted range of the document
+type Highlight = (Range<usize>, Class, u32);
+
+/// Classifies the `tokens` of `text`, using the resolved `symbols` to tell
+/// locals, arguments, consts and definitions apart, and encodes the ones
+/// overlapping `range` (or all of them) relative to each other, as the
+/// protocol wants them
+pub(crate) fn encode(
+    text: &str,
+    tokens: &[TokenSpan],
+    symbols: Option<&SymbolTable>,
+    range: Option<&Range<usize>>,
+) -> Vec<SemanticToken> {
+    let mut highlights = comments(tokens);
+    highlights.extend(code(tokens, symbols));
+    highlights.sort_by_key(|(range, _, _)| range.start);
+
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let mut encoded = Vec::new();
+    let (mut last_line, mut last_start) = (0, 0);
+    for (highlight, kind, modifiers) in highlights {
+        if range.map_or(false, |r| {
+            highlight.end < r.start || r.end < highlight.start
+        }) {
+            continue;
+        }
+        // tokens like heredocs span lines, but clients expect one per line
+        for (line, start, length) in lines(text, &line_starts, highlight) {
+            let delta_line = line - last_line;
+            let delta_start = if delta_line == 0 {
+                start - last_start
+            } else {
+                start
+            };
+            encoded.push(SemanticToken {
+                delta_line,
+                delta_start,
+                length,
+                token_type: kind as u32,
+                token_modifiers_bitset: modifiers,
+            });
+            (last_line, last_start) = (line, start);
+        }
+    }
+    encoded
+}
+
+/// The edits turning the `old` tokens into the `new` ones
+#[allow(clippy::cast_possible_truncation)]
+pub(crate) fn edits(old: &[SemanticToken], new: &[SemanticToken]) -> Vec<SemanticTokensEdit> {
+    let prefix = old.iter().zip(new).take_while(|(o, n)| o == n).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(o, n)| o == n)
+        .count();
+    let (deleted, inserted) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+    if deleted.is_empty() && inserted.is_empty() {
+        return vec![];
+    }
+    // edits count the integers of the encoded tokens, five per token
+    vec![SemanticTokensEdit {
+        start: prefix as u32 * 5,
+        delete_count: deleted.len() as u32 * 5,
+        data: Some(inserted.to_vec()),
+    }]
+}
+
+// the (zero-based) line, UTF-16 start column and length of each line `range`
+// spans, leaving out line breaks
+#[allow(clippy::cast_possible_truncation)]
+fn lines(text: &str, line_starts: &[usize], range: Range<usize>) -> Vec<(u32, u32, u32)> {
+    let Some(content) = text.get(range.clone()) else {
+        return vec![];
+    };
+    let mut offset = range.start;
+    let mut lines = Vec::new();
+    for segment in content.split_inclusive('\n') {
+        let trimmed = segment.trim_end_matches(['\n', '\r']);
+        if !trimmed.is_empty() {
+            let line = line_starts.partition_point(|start| *start <= offset) - 1;
+            let column = text[line_starts[line]..offset].encode_utf16().count();
+            lines.push((
+                line as u32,
+                column as u32,
+                trimmed.encode_utf16().count() as u32,
+            ));
+        }
+        offset += segment.len();
+    }
+    lines
+}
+
+fn span(token: &TokenSpan) -> Range<usize> {
+    token.span.start().absolute()..token.span.end().absolute()
+}
+
+fn comments(tokens: &[TokenSpan]) -> Vec<Highlight> {
+    tokens
+        .iter()
+        .filter_map(|token| match token.value {
+            Token::SingleLineComment(_) => Some((span(token), Class::Comment, 0)),
+            Token::DocComment(_) | Token::ModComment(_) => {
+                Some((span(token), Class::Comment, DOCUMENTATION))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn code(tokens: &[TokenSpan], symbols: Option<&SymbolTable>) -> Vec<Highlight> {
+    // what the identifiers at an offset resolve to, and whether they are
+    // defined there
+    let mut resolved = HashMap::new();
+    if let Some(symbols) = symbols {
+        for symbol in symbols.symbols() {
+            resolved.insert(symbol.selection.start, (symbol.kind, true));
+        }
+        for reference in symbols.references() {
+            resolved
+                .entry(reference.range.start)
+                .or_insert((symbols.symbols()[reference.symbol].kind, false));
+        }
+    }
+
+    let tokens: Vec<&TokenSpan> = tokens.iter().filter(|t| !is_trivia(&t.value)).collect();
+    // open braces, and whether they start a string interpolation
+    let mut braces = Vec::new();
+    let mut highlights = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let prev = i.checked_sub(1).map(|p| &tokens[p].value);
+        let next = tokens.get(i + 1).map(|t| &t.value);
+        let highlight = match &token.value {
+            Token::LBrace | Token::LPatBrace => {
+                braces.push(false);
+                None
+            }
+            Token::Interpol => {
+                braces.push(true);
+                Some((Class::Operator, 0))
+            }
+            Token::RBrace => braces
+                .pop()
+                .unwrap_or(false)
+                .then_some((Class::Operator, 0)),
+            Token::Ident(_, escaped) => {
+                let start = token.span.start().absolute() + usize::from(*escaped);
+                Some(identifier(prev, next, resolved.get(&start).copied()))
+            }
+            Token::Event | Token::State | Token::Dollar => Some((Class::Variable, DEFAULT_LIBRARY)),
+            Token::DQuote
+            | Token::StringLiteral(_)
+            | Token::HereDocStart
+            | Token::HereDocEnd
+            | Token::HereDocLiteral(_)
+            | Token::EscapedHash => Some((Class::String, 0)),
+            Token::IntLiteral(_) | Token::FloatLiteral(..) => Some((Class::Number, 0)),
+            Token::TestLiteral(..) => Some((Class::Regexp, 0)),
+            Token::Nil | Token::BoolLiteral(_) | Token::DontCare => Some((Class::Keyword, 0)),
+            t if is_operator(t) => Some((Class::Operator, 0)),
+            t if is_keyword(t) => Some((Class::Keyword, 0)),
+            _ => None,
+        };
+        if let Some((kind, modifiers)) = highlight {
+            highlights.push((span(token), kind, modifiers));
+        }
+    }
+    highlights
+}
+
+// classifies an identifier by what it refers to or, for the ones that aren't
+// resolved, by the tokens around it
+fn identifier(
+    prev: Option<&Token>,
+    next: Option<&Token>,
+    resolved: Option<(Kind, bool)>,
+) -> (Class, u32) {
+    match (prev, next, resolved) {
+        // fields of records, `event`, `state` and `$`
+        (Some(Token::Dot | Token::Dollar), _, _) => (Class::Property, 0),
+        (_, Some(Token::ColonColon), _) => (Class::Namespace, 0),
+        // extractors like `re|...|`
+        (_, Some(Token::TestLiteral(..)), _) => (Class::Function, DEFAULT_LIBRARY),
+        (_, _, Some((kind, definition))) => {
+            let (kind, modifiers) = symbol(kind);
+            (kind, modifiers | if definition { DECLARATION } else { 0 })
+        }
+        // members of modules
+        (Some(Token::ColonColon), Some(Token::LParen), None) => (Class::Function, 0),
+        (Some(Token::ColonColon), _, None) => (Class::Variable, READONLY),
+        (_, Some(Token::LParen), None) => (Class::Function, 0),
+        // configuration in `with` and `args` blocks
+        (_, Some(Token::Eq), None) => (Class::Property, 0),
+        _ => (Class::Variable, 0),
+    }
+}
+
+fn symbol(kind: Kind) -> (Class, u32) {
+    match kind {
+        Kind::Module => (Class::Namespace, 0),
+        Kind::Const => (Class::Variable, READONLY),
+        Kind::Function => (Class::Function, 0),
+        Kind::Argument => (Class::Parameter, 0),
+        Kind::Window
+        | Kind::Operator
+        | Kind::Script
+        | Kind::Pipeline
+        | Kind::Connector
+        | Kind::Flow => (Class::Type, 0),
+        Kind::Local | Kind::Stream | Kind::Instance | Kind::Select | Kind::Deployment => {
+            (Class::Variable, 0)
+        }
+    }
+}
+
+fn is_operator(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Not
+            | Token::BitNot
+            | Token::And
+            | Token::Or
+            | Token::Xor
+            | Token::BitAnd
+            | Token::BitXor
+            | Token::Eq
+            | Token::EqEq
+            | Token::NotEq
+            | Token::TildeEq
+            | Token::Tilde
+            | Token::Gte
+            | Token::Gt
+            | Token::Lte
+            | Token::Lt
+            | Token::RBitShiftSigned
+            | Token::RBitShiftUnsigned
+            | Token::LBitShift
+            | Token::Add
+            | Token::Sub
+            | Token::Mul
+            | Token::Div
+            | Token::Mod
+            | Token::EqArrow
+    )
+}
+
+// the keywords of the lexer, except `event` and `state` which are highlighted
+// as variables
+fn is_keyword(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Absent
+            | Token::Args
+            | Token::As
+            | Token::By
+            | Token::Case
+            | Token::ConfigDirective
+            | Token::Connect
+            | Token::Connector
+            | Token::Const
+            | Token::Copy
+            | Token::Create
+            | Token::Default
+            | Token::Define
+            | Token::Deploy
+            | Token::Drop
+            | Token::Each
+            | Token::Emit
+            | Token::End
+            | Token::Erase
+            | Token::Flow
+            | Token::For
+            | Token::From
+            | Token::Fun
+            | Token::Group
+            | Token::Having
+            | Token::Insert
+            | Token::Into
+            | Token::Intrinsic
+            | Token::Let
+            | Token::Match
+            | Token::Merge
+            | Token::Module
+            | Token::Move
+            | Token::Of
+            | Token::Operator
+            | Token::Patch
+            | Token::Pipeline
+            | Token::Present
+            | Token::Recur
+            | Token::Script
+            | Token::Select
+            | Token::Set
+            | Token::Sliding
+            | Token::Stream
+            | Token::To
+            | Token::Tumbling
+            | Token::Update
+            | Token::Upsert
+            | Token::Use
+            | Token::When
+            | Token::Where
+            | Token::Window
+            | Token::With
+    )
+}
@@ -0,0 +1,191 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Keywords and snippets for the constructs of the tremor languages. What is
+// offered is up to the grammar: keywords where the parser expects them, and
+// snippets where the keywords they start with parse. Some keywords come with
+// short reference text too, for hovers.
+
+use crate::language::Token;
+use tremor_script::arena::Arena;
+use tremor_script::lexer::Lexer;
+
+/// A snippet for a construct of a tremor language
+pub(crate) struct Snippet {
+    /// the keywords the construct starts with
+    pub(crate) lead: &'static str,
+    pub(crate) detail: &'static str,
+    pub(crate) body: &'static str,
+}
+
+// https://microsoft.github.io/language-server-protocol/specifications/specification-3-14/#snippet-syntax
+pub(crate) const SNIPPETS: &[Snippet] = &[
+    Snippet {
+        lead: "match",
+        detail: "match … of case … end",
+        body: "match ${1:event} of\n  case ${2:%{\\}} => ${3:event}\n  case _ => ${4:null}\nend",
+    },
+    Snippet {
+        lead: "patch",
+        detail: "patch … of … end",
+        body: "patch ${1:event} of\n  insert \"${2:key}\" => ${3:null}\nend",
+    },
+    Snippet {
+        lead: "merge",
+        detail: "merge … of … end",
+        body: "merge ${1:event} of ${2:{\\}} end",
+    },
+    Snippet {
+        lead: "for",
+        detail: "for … of case … end",
+        body: "for ${1:event} of\n  case (${2:key}, ${3:value}) => ${3:value}\nend",
+    },
+    Snippet {
+        lead: "let",
+        detail: "let … = …",
+        body: "let ${1:name} = ${2:event}",
+    },
+    Snippet {
+        lead: "const",
+        detail: "const … = …;",
+        body: "const ${1:NAME} = ${2:null};",
+    },
+    Snippet {
+        lead: "fn",
+        detail: "fn …(…) with … end;",
+        body: "fn ${1:name}(${2}) with\n  ${3:null}\nend;",
+    },
+    Snippet {
+        lead: "use",
+        detail: "use …;",
+        body: "use ${1:module};",
+    },
+    Snippet {
+        lead: "select",
+        detail: "select … from … into …;",
+        body: "select ${1:event} from ${2:in} into ${3:out};",
+    },
+    Snippet {
+        lead: "define window",
+        detail: "define window … from tumbling with … end;",
+        body: "define window ${1:name} from tumbling\nwith\n  ${2:interval} = ${3:1_000_000_000}\nend;",
+    },
+    Snippet {
+        lead: "define script",
+        detail: "define script … script … end;",
+        body: "define script ${1:name}\nscript\n  ${2:event}\nend;",
+    },
+    Snippet {
+        lead: "define operator",
+        detail: "define operator … from … with … end;",
+        body: "define operator ${1:name} from ${2:generic::batch}\nwith\n  ${3:count} = ${4:10}\nend;",
+    },
+    Snippet {
+        lead: "define pipeline",
+        detail: "define pipeline … pipeline … end;",
+        body: "define pipeline ${1:name}\npipeline\n  ${2:select event from in into out;}\nend;",
+    },
+    Snippet {
+        lead: "define connector",
+        detail: "define connector … from … with … end;",
+        body: "define connector ${1:name} from ${2:metronome}\nwith\n  config = ${3:{\\}}\nend;",
+    },
+    Snippet {
+        lead: "define flow",
+        detail: "define flow … flow … end;",
+        body: "define flow ${1:name}\nflow\n  ${2}\nend;",
+    },
+    Snippet {
+        lead: "create stream",
+        detail: "create stream …;",
+        body: "create stream ${1:name};",
+    },
+    Snippet {
+        lead: "create operator",
+        detail: "create operator … from …;",
+        body: "create operator ${1:name} from ${2:definition};",
+    },
+    Snippet {
+        lead: "create script",
+        detail: "create script … from …;",
+        body: "create script ${1:name} from ${2:definition};",
+    },
+    Snippet {
+        lead: "create pipeline",
+        detail: "create pipeline … from …;",
+        body: "create pipeline ${1:name} from ${2:definition};",
+    },
+    Snippet {
+        lead: "create connector",
+        detail: "create connector … from …;",
+        body: "create connector ${1:name} from ${2:definition};",
+    },
+    Snippet {
+        lead: "connect",
+        detail: "connect /connector/… to /pipeline/…;",
+        body: "connect /connector/${1:name} to /pipeline/${2:name};",
+    },
+    Snippet {
+        lead: "deploy flow",
+        detail: "deploy flow …;",
+        body: "deploy flow ${1:name};",
+    },
+];
+
+/// Short reference text for keywords whose meaning isn't obvious from the
+/// code, in markdown
+pub(crate) fn keyword_doc(token: &Token) -> Option<&'static str> {
+    Some(match token {
+        Token::Patch => {
+            "`patch target of ... end`\n\nReturns a copy of the record `target` with the \
+            operations applied in order: `insert`, `upsert`, `update`, `erase`, `move`, \
+            `copy`, `merge` and `default`."
+        }
+        Token::Merge => {
+            "`merge target of value end`\n\nMerges the record `value` into `target`, as a \
+            JSON merge patch (RFC 7396): records are merged recursively, other values \
+            replaced and keys set to `null` removed."
+        }
+        Token::Emit => {
+            "`emit value => \"port\"`\n\nStops the script and sends `value` (`event` if \
+            left out) to the `out` port, or to the given port."
+        }
+        Token::Drop => "`drop`\n\nStops the script and discards the event, emitting nothing.",
+        _ => return None,
+    })
+}
+
+/// The keywords among the tokens expected by the parser, which quotes them
+/// like "`match`"
+pub(crate) fn keywords(expected: &[String]) -> Vec<&str> {
+    expected
+        .iter()
+        .filter_map(|token| token.strip_prefix('`')?.strip_suffix('`'))
+        .filter(|word| word.chars().all(|c| c.is_ascii_lowercase()) && is_keyword(word))
+        .collect()
+}
+
+// whether `word` is lexed as a keyword, rather than as an identifier
+fn is_keyword(word: &str) -> bool {
+    let Ok((aid, text)) = Arena::insert(word) else {
+        return false;
+    };
+    let tokens: Vec<Token> = Lexer::new(text, aid)
+        .filter_map(|t| Some(t.ok()?.value))
+        .collect();
+    let keyword = matches!(tokens.as_slice(), [token] if !matches!(token, Token::Ident(..)));
+    drop(tokens);
+    unsafe { Arena::delte_index_this_is_really_unsafe_dont_use_it(aid).unwrap() };
+    keyword
+}
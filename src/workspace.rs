@@ -0,0 +1,287 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{language, lsp_utils};
+use halfbrown::HashMap;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use tower_lsp::lsp_types::{Location, Url};
+use tremor_script::path::ModulePath;
+
+/// Resolves the file a module is loaded from, like tremor does, against the
+/// `module_path`
+pub(crate) fn module_file(module: &[String], module_path: &ModulePath) -> Option<PathBuf> {
+    let path: PathBuf = module.iter().collect();
+    language::FILE_EXTENSIONS
+        .iter()
+        .find_map(|extension| module_path.resolve(path.with_extension(extension)))
+}
+
+/// Resolves the file a module is loaded from, like [`module_file`], or else
+/// against the workspace `folders`
+pub(crate) fn resolve_module(
+    module: &[String],
+    module_path: &ModulePath,
+    folders: &[PathBuf],
+) -> Option<PathBuf> {
+    module_file(module, module_path).or_else(|| {
+        let path: PathBuf = module.iter().collect();
+        folders.iter().find_map(|folder| {
+            language::FILE_EXTENSIONS
+                .iter()
+                .map(|extension| folder.join(&path).with_extension(extension))
+                .find(|file| file.is_file())
+        })
+    })
+}
+
+/// The module a file provides, relative to the `module_path` or to one of the
+/// workspace `folders`
+pub(crate) fn module_of(
+    file: &Path,
+    module_path: &ModulePath,
+    folders: &[PathBuf],
+) -> Option<Vec<String>> {
+    module_folders(module_path)
+        .into_iter()
+        .chain(folders.iter().cloned())
+        .find_map(|root| {
+            let module = file.strip_prefix(root).ok()?.with_extension("");
+            module
+                .iter()
+                .map(|segment| segment.to_str().map(ToString::to_string))
+                .collect()
+        })
+}
+
+/// The folders of the `module_path`
+pub(crate) fn module_folders(module_path: &ModulePath) -> Vec<PathBuf> {
+    module_path.mounts.iter().map(PathBuf::from).collect()
+}
+
+// folders of version control and build tools, which hold no sources of
+// their own
+const IGNORED_FOLDERS: [&str; 3] = [".git", "target", "node_modules"];
+
+/// All tremor files in `folders`, following symbolic links to folders that
+/// weren't visited yet
+pub(crate) fn files(folders: &[PathBuf]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut visited = HashSet::new();
+    let mut pending = folders.to_vec();
+    while let Some(dir) = pending.pop() {
+        let Ok(canonical) = dir.canonicalize() else {
+            continue;
+        };
+        if !visited.insert(canonical) {
+            continue;
+        }
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for path in entries.filter_map(|entry| Some(entry.ok()?.path())) {
+            if path.is_dir() {
+                let ignored = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .map_or(false, |name| IGNORED_FOLDERS.contains(&name));
+                if !ignored {
+                    pending.push(path);
+                }
+            } else if path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .map_or(false, |extension| {
+                    language::FILE_EXTENSIONS.contains(&extension)
+                })
+            {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// A symbol in the workspace index
+#[derive(Clone, Debug)]
+pub(crate) struct Symbol {
+    pub(crate) name: String,
+    pub(crate) kind: language::Kind,
+    pub(crate) location: Location,
+    /// name of the definition the symbol is made in
+    pub(crate) container: Option<String>,
+}
+
+/// Index of the symbols defined in the files of the workspace and of the
+/// module path, and of the modules they use
+#[derive(Debug, Default)]
+pub(crate) struct Index {
+    files: HashMap<Url, Vec<Symbol>>,
+    // the module files each file uses, making up the dependency graph
+    uses: HashMap<Url, Vec<Url>>,
+}
+
+impl Index {
+    /// Replaces the symbols of a file, and the modules it uses
+    pub(crate) fn update(&mut self, uri: Url, symbols: Vec<Symbol>, uses: Vec<Url>) {
+        self.uses.insert(uri.clone(), uses);
+        self.files.insert(uri, symbols);
+    }
+
+    /// Removes the symbols of all files in `folder`
+    pub(crate) fn remove_folder(&mut self, folder: &Path) {
+        let outside = |uri: &Url| {
+            uri.to_file_path()
+                .map_or(true, |file| !file.starts_with(folder))
+        };
+        self.files.retain(|uri, _| outside(uri));
+        self.uses.retain(|uri, _| outside(uri));
+    }
+
+    /// Removes the symbols of a file
+    pub(crate) fn remove(&mut self, uri: &Url) {
+        self.files.remove(uri);
+        self.uses.remove(uri);
+    }
+
+    /// The files using the module at `uri`, directly or through other modules
+    pub(crate) fn dependents(&self, uri: &Url) -> Vec<Url> {
+        let mut dependents: Vec<Url> = Vec::new();
+        let mut pending = vec![uri];
+        while let Some(module) = pending.pop() {
+            for (file, uses) in &self.uses {
+                if uses.contains(module) && file != uri && !dependents.contains(file) {
+                    dependents.push(file.clone());
+                    pending.push(file);
+                }
+            }
+        }
+        dependents.sort();
+        dependents
+    }
+
+    /// The symbols matching `query`, best and shortest matches first
+    pub(crate) fn search(&self, query: &str) -> Vec<&Symbol> {
+        let mut matches: Vec<(usize, &Symbol)> = self
+            .files
+            .values()
+            .flatten()
+            .filter_map(|symbol| Some((fuzzy_score(query, &symbol.name)?, symbol)))
+            .collect();
+        matches.sort_by(|(a, a_symbol), (b, b_symbol)| {
+            b.cmp(a)
+                .then_with(|| a_symbol.name.len().cmp(&b_symbol.name.len()))
+                .then_with(|| a_symbol.name.cmp(&b_symbol.name))
+                .then_with(|| a_symbol.location.uri.cmp(&b_symbol.location.uri))
+        });
+        matches.into_iter().map(|(_, symbol)| symbol).collect()
+    }
+}
+
+/// The symbols of a document that are listed in the index
+pub(crate) fn index_symbols(uri: &Url, text: &str, symbols: &language::SymbolTable) -> Vec<Symbol> {
+    fn walk(
+        uri: &Url,
+        text: &str,
+        symbols: &language::SymbolTable,
+        outline: &[language::Outline],
+        container: Option<&str>,
+        indexed: &mut Vec<Symbol>,
+    ) {
+        for o in outline {
+            let symbol = &symbols.symbols()[o.symbol];
+            if is_indexed(symbol.kind) {
+                indexed.push(Symbol {
+                    name: symbol.name.clone(),
+                    kind: symbol.kind,
+                    location: Location::new(
+                        uri.clone(),
+                        lsp_utils::to_lsp_range(text, &symbol.selection),
+                    ),
+                    container: container.map(ToString::to_string),
+                });
+            }
+            walk(uri, text, symbols, &o.children, Some(&symbol.name), indexed);
+        }
+    }
+    let mut indexed = Vec::new();
+    walk(uri, text, symbols, &symbols.outline(), None, &mut indexed);
+    indexed
+}
+
+/// The files of the modules a document uses
+pub(crate) fn index_uses(
+    symbols: &language::SymbolTable,
+    module_path: &ModulePath,
+    folders: &[PathBuf],
+) -> Vec<Url> {
+    let mut uses: Vec<Url> = symbols
+        .symbols()
+        .iter()
+        .filter_map(|symbol| resolve_module(symbol.module.as_ref()?, module_path, folders))
+        .filter_map(|file| Url::from_file_path(file).ok())
+        .collect();
+    uses.sort();
+    uses.dedup();
+    uses
+}
+
+// only definitions are indexed, not the statements and locals of a document
+fn is_indexed(kind: language::Kind) -> bool {
+    use language::Kind;
+    matches!(
+        kind,
+        Kind::Flow
+            | Kind::Pipeline
+            | Kind::Connector
+            | Kind::Window
+            | Kind::Operator
+            | Kind::Script
+            | Kind::Function
+            | Kind::Const
+    )
+}
+
+/// Scores how well `query` matches `name`, case insensitively: all characters
+/// of the query have to appear in the name in order, and matches of
+/// consecutive characters or at the start of words score higher
+pub(crate) fn fuzzy_score(query: &str, name: &str) -> Option<usize> {
+    let mut query = query.chars().flat_map(char::to_lowercase).peekable();
+    let mut score = 0;
+    let mut consecutive = false;
+    let mut previous = None;
+    for c in name.chars() {
+        let Some(q) = query.peek() else {
+            break;
+        };
+        if c.to_lowercase().eq(std::iter::once(*q)) {
+            score += 1;
+            if consecutive {
+                score += 2;
+            }
+            if previous.map_or(true, |p: char| {
+                p == '_' || (p.is_lowercase() && c.is_uppercase())
+            }) {
+                score += 3;
+            }
+            consecutive = true;
+            query.next();
+        } else {
+            consecutive = false;
+        }
+        previous = Some(c);
+    }
+    query.peek().is_none().then_some(score)
+}